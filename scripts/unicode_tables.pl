@@ -0,0 +1,106 @@
+#!/usr/bin/env perl
+# src/unicode_tables.rs を生成する
+# perl scripts/unicode_tables.pl > src/unicode_tables.rs
+use strict;
+use warnings;
+use Unicode::UCD qw(prop_values prop_value_aliases prop_invlist prop_invmap);
+
+my @binary = qw(
+    Alphabetic White_Space Uppercase Lowercase
+    ID_Start ID_Continue XID_Start XID_Continue
+);
+
+# 反転リストを (start, end) の範囲にし、サロゲートを除く
+sub ranges {
+    my @invlist = prop_invlist($_[0]);
+    my @ranges;
+    while (@invlist) {
+        my $start = shift @invlist;
+        my $end = @invlist ? (shift @invlist) - 1 : 0x10FFFF;
+        for my $range ([$start, $end < 0xD7FF ? $end : 0xD7FF], [$start > 0xE000 ? $start : 0xE000, $end]) {
+            my ($s, $e) = @$range;
+            push @ranges, [$s, $e] if $s <= $e && ($e < 0xD800 || $s > 0xDFFF);
+        }
+    }
+    return @ranges;
+}
+
+sub const_name {
+    my $name = uc shift;
+    $name =~ s/[^A-Z0-9]/_/g;
+    return $name;
+}
+
+my @tables;
+for my $gc (prop_values("gc")) {
+    my @names = prop_value_aliases("gc", $gc);
+    push @tables, ["GeneralCategory", "GC_" . const_name($names[1]), [@names], [ranges("gc=$gc")]];
+}
+# Katakana_Or_Hiragana のように別名の無いものや、空の文字種は除く
+my @scripts = grep { my @names = prop_value_aliases("sc", $_); @names > 1 } prop_values("sc");
+for my $sc (sort { (prop_value_aliases("sc", $a))[1] cmp (prop_value_aliases("sc", $b))[1] } @scripts) {
+    my @names = prop_value_aliases("sc", $sc);
+    my @ranges = ranges("sc=$sc");
+    next unless @ranges;
+    push @tables, ["Script", "SC_" . const_name($names[1]), [@names], [@ranges]];
+}
+for my $name (@binary) {
+    push @tables, ["Binary", const_name($name), [$name], [ranges($name)]];
+}
+
+# 単純ケースフォールディングで同じ文字になる文字をまとめる
+my %orbits;
+{
+    my ($list, $map) = prop_invmap("Simple_Case_Folding");
+    for my $i (0 .. $#$list - 1) {
+        next if $map->[$i] == 0;
+        for my $cp ($list->[$i] .. $list->[$i + 1] - 1) {
+            my $folded = $map->[$i] + $cp - $list->[$i];
+            $orbits{$folded}{$folded} = 1;
+            $orbits{$folded}{$cp} = 1;
+        }
+    }
+}
+my @case_fold;
+for my $orbit (values %orbits) {
+    my @chars = sort { $a <=> $b } keys %$orbit;
+    for my $cp (@chars) {
+        push @case_fold, [$cp, [grep { $_ != $cp } @chars]];
+    }
+}
+@case_fold = sort { $a->[0] <=> $b->[0] } @case_fold;
+
+print "//! Unicodeの文字の集合の表\n";
+print "//! `scripts/unicode_tables.pl`で生成したもの 手で編集しないこと\n";
+print "use crate::unicode::{Property, PropertyKind};\n\n";
+printf "pub const UNICODE_VERSION: &str = \"%s\";\n\n", Unicode::UCD::UnicodeVersion();
+print "pub const PROPERTIES: &[Property] = &[\n";
+for my $table (@tables) {
+    my ($kind, $const, $names) = @$table;
+    my $names_str = join(", ", map { "\"$_\"" } @$names);
+    print "    Property {\n";
+    print "        kind: PropertyKind::$kind,\n";
+    print "        names: &[$names_str],\n";
+    print "        ranges: $const,\n";
+    print "    },\n";
+}
+print "];\n\n";
+print "/// 単純ケースフォールディングで同じになる文字 (文字の昇順)\n";
+print "#[rustfmt::skip]\n";
+print "pub const CASE_FOLD: &[(char, &[char])] = &[\n";
+for my $entry (@case_fold) {
+    my ($cp, $others) = @$entry;
+    my $others_str = join(", ", map { sprintf("'\\u{%X}'", $_) } @$others);
+    printf "    ('\\u{%X}', &[%s]),\n", $cp, $others_str;
+}
+print "];\n";
+for my $table (@tables) {
+    my ($kind, $const, $names, $ranges) = @$table;
+    print "\n#[rustfmt::skip]\n";
+    print "const $const: &[(char, char)] = &[\n";
+    my @items = map { sprintf("('\\u{%X}', '\\u{%X}')", @$_) } @$ranges;
+    while (my @line = splice(@items, 0, 3)) {
+        print "    ", join(", ", @line), ",\n";
+    }
+    print "];\n";
+}
@@ -0,0 +1,242 @@
+//! 文字クラスの実装
+//! `[a-z0-9_]`のような文字の集合を、ソート済みで重なりのない範囲の列として持つ
+use crate::unicode::case_variants;
+use crate::unicode_tables::CASE_FOLD;
+
+/// 文字の範囲の集合
+/// `ranges`は常にソート済みで、重なりも隣接もしない状態に保つ
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CharClass {
+    ranges: Vec<(char, char)>,
+}
+
+/// 次のコードポイント (サロゲートは飛ばす)
+pub(crate) fn next_char(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// 前のコードポイント (サロゲートは飛ばす)
+fn prev_char(c: char) -> Option<char> {
+    match c {
+        '\u{E000}' => Some('\u{D7FF}'),
+        '\0' => None,
+        _ => char::from_u32(c as u32 - 1),
+    }
+}
+
+impl CharClass {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_char(c: char) -> Self {
+        Self::from_ranges([(c, c)])
+    }
+
+    pub fn from_ranges<I: IntoIterator<Item = (char, char)>>(ranges: I) -> Self {
+        let mut class = Self::new();
+        ranges
+            .into_iter()
+            .for_each(|(start, end)| class.push(start, end));
+        class
+    }
+
+    /// 全ての文字を含むクラス
+    pub fn any() -> Self {
+        Self::from_ranges([('\0', char::MAX)])
+    }
+
+    /// `[:alpha:]`のようなPOSIXクラス (ASCIIの範囲だけ)
+    pub fn posix(name: &str) -> Option<Self> {
+        let ranges: &[(char, char)] = match name {
+            "alnum" => &[('0', '9'), ('A', 'Z'), ('a', 'z')],
+            "alpha" => &[('A', 'Z'), ('a', 'z')],
+            "blank" => &[('\t', '\t'), (' ', ' ')],
+            "cntrl" => &[('\0', '\u{1F}'), ('\u{7F}', '\u{7F}')],
+            "digit" => &[('0', '9')],
+            "graph" => &[('!', '~')],
+            "lower" => &[('a', 'z')],
+            "print" => &[(' ', '~')],
+            "punct" => &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+            "space" => &[('\t', '\r'), (' ', ' ')],
+            "upper" => &[('A', 'Z')],
+            "xdigit" => &[('0', '9'), ('A', 'F'), ('a', 'f')],
+            _ => return None,
+        };
+        Some(Self::from_ranges(ranges.iter().copied()))
+    }
+
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// `start`から`end`までの範囲を追加する
+    pub fn push(&mut self, start: char, end: char) {
+        assert!(start <= end, "invalid range {:?}-{:?}", start, end);
+        self.ranges.push((start, end));
+        self.normalize();
+    }
+
+    pub fn union(&mut self, other: &Self) {
+        self.ranges.extend_from_slice(&other.ranges);
+        self.normalize();
+    }
+
+    /// 大文字小文字を区別しないよう、含まれる文字と
+    /// 単純ケースフォールディングで同じになる文字を全て加えたクラスを返す
+    pub fn case_fold(&self) -> Self {
+        let mut class = self.clone();
+        let len = self
+            .ranges
+            .iter()
+            .map(|&(start, end)| (end as u32 - start as u32) as usize + 1)
+            .sum::<usize>();
+        // 文字の少ないクラスは一文字ずつ表を引き、多いクラスは表を一度だけなめる
+        let variants = if len < CASE_FOLD.len() {
+            self.ranges
+                .iter()
+                .flat_map(|&(start, end)| start..=end)
+                .flat_map(case_variants)
+                .collect::<Vec<_>>()
+        } else {
+            CASE_FOLD
+                .iter()
+                .filter(|(c, _)| self.contains(*c))
+                .flat_map(|(_, variants)| variants.iter())
+                .collect()
+        };
+        for &variant in variants {
+            class.ranges.push((variant, variant));
+        }
+        class.normalize();
+        class
+    }
+
+    /// 補集合を返す
+    pub fn negate(&self) -> Self {
+        let mut ranges = Vec::new();
+        let mut lower = Some('\0');
+        for &(start, end) in &self.ranges {
+            if let (Some(low), Some(high)) = (lower, prev_char(start)) {
+                if low <= high {
+                    ranges.push((low, high));
+                }
+            }
+            lower = next_char(end);
+        }
+        if let Some(low) = lower {
+            ranges.push((low, char::MAX));
+        }
+        Self { ranges }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    std::cmp::Ordering::Less
+                } else if c < start {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    fn normalize(&mut self) {
+        self.ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(self.ranges.len());
+        for &(start, end) in &self.ranges {
+            match merged.last_mut() {
+                Some((_, last_end)) if next_char(*last_end).is_none_or(|n| start <= n) => {
+                    *last_end = (*last_end).max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        self.ranges = merged;
+    }
+}
+
+#[cfg(test)]
+mod char_class_test {
+    use super::*;
+
+    #[test]
+    fn merge_overlap() {
+        let class = CharClass::from_ranges([('a', 'f'), ('d', 'k'), ('x', 'z')]);
+        assert_eq!(class.ranges(), &[('a', 'k'), ('x', 'z')]);
+    }
+
+    #[test]
+    fn merge_adjacent() {
+        let class = CharClass::from_ranges([('0', '9'), ('a', 'c'), ('d', 'd')]);
+        assert_eq!(class.ranges(), &[('0', '9'), ('a', 'd')]);
+    }
+
+    #[test]
+    fn contains() {
+        let class = CharClass::from_ranges([('a', 'z'), ('0', '9'), ('_', '_')]);
+        assert!(class.contains('q'));
+        assert!(class.contains('_'));
+        assert!(class.contains('0'));
+        assert!(!class.contains('A'));
+        assert!(!class.contains('-'));
+    }
+
+    #[test]
+    fn negate() {
+        let class = CharClass::from_ranges([('\0', '/'), (':', char::MAX)]);
+        assert_eq!(class.negate().ranges(), &[('0', '9')]);
+        assert_eq!(class.negate().negate(), class);
+        assert_eq!(CharClass::new().negate(), CharClass::any());
+        assert!(CharClass::any().negate().is_empty());
+    }
+
+    #[test]
+    fn posix() {
+        let punct = CharClass::posix("punct").unwrap();
+        let alnum = CharClass::posix("alnum").unwrap();
+        let space = CharClass::posix("space").unwrap();
+        let mut printable = punct.clone();
+        printable.union(&alnum);
+        printable.push(' ', ' ');
+        assert_eq!(printable, CharClass::posix("print").unwrap());
+        assert!(space.contains('\u{B}'));
+        assert!(CharClass::posix("Alpha").is_none());
+    }
+
+    #[test]
+    fn case_fold() {
+        let class = CharClass::from_ranges([('a', 'c'), ('1', '1')]).case_fold();
+        assert_eq!(class.ranges(), &[('1', '1'), ('A', 'C'), ('a', 'c')]);
+        let kelvin = CharClass::from_char('K').case_fold();
+        assert!(kelvin.contains('k'));
+        assert!(kelvin.contains('\u{212A}'));
+        // 表より文字の多いクラスは表をなめる
+        let large = CharClass::from_ranges([('\0', 'z'), ('\u{3000}', '\u{3FFF}')]).case_fold();
+        assert!(large.contains('\u{212A}'));
+        assert!(large.contains('\u{17F}'));
+        assert!(!large.contains('λ'));
+    }
+
+    #[test]
+    fn negate_skips_surrogate() {
+        let class = CharClass::from_char('\u{D7FF}').negate();
+        assert!(!class.contains('\u{D7FF}'));
+        assert!(class.contains('\u{E000}'));
+        assert_eq!(
+            class.ranges(),
+            &[('\0', '\u{D7FE}'), ('\u{E000}', char::MAX)]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! 仕様から字句解析器のRustコードを生成する
+//! 生成したコードはflexに依存せず、それだけでコンパイルできる
+//!
+//! 最小DFAを`BOUNDARIES` (区間の先頭の文字)、`TRANSITIONS` (遷移表)、
+//! `ACCEPTS` (受理するトークン)、`TRAILING` (`r/s`の区切り方)、
+//! `AT_END` (入力の終わりで受理するトークン) の定数として書き出し、`scan`で走らせる
+//! `Lexer`は`scan`を繰り返して入力全体をトークンに分ける
+//! スタート状態ごとに`STARTS`の開始状態から走らせ、`Lexer::begin`などで切り替える
+//! 行頭では`^`のルールも使える`BOL_STARTS`の開始状態から走らせる
+//! `Lexer`は入力の終わりで、スタート状態ごとの`EOF`のトークンかエラーを一度だけ返す
+use std::fmt::Write;
+
+use crate::config::{ConfigError, Configs};
+use crate::dfa::Dfa;
+use crate::nfa::TrailingContext;
+
+/// 状態数が収まる一番小さい符号なし整数型
+fn state_type(len: usize) -> &'static str {
+    if len <= u8::MAX as usize + 1 {
+        "u8"
+    } else if len <= u16::MAX as usize + 1 {
+        "u16"
+    } else {
+        "u32"
+    }
+}
+
+/// 要素を一行に`per_line`個ずつ並べる
+fn write_list<I: IntoIterator<Item = String>>(code: &mut String, items: I, per_line: usize) {
+    let items = items.into_iter().collect::<Vec<_>>();
+    for line in items.chunks(per_line) {
+        writeln!(code, "    {},", line.join(", ")).unwrap();
+    }
+}
+
+/// トークンの表の要素
+fn token_item(configs: &Configs, terminal: &Option<usize>) -> String {
+    match terminal {
+        Some(rule) => format!("Some(Token::{})", configs.rules()[*rule].name()),
+        None => "None".to_string(),
+    }
+}
+
+fn write_tables(code: &mut String, configs: &Configs, dfa: &Dfa<usize>) {
+    let width = dfa.boundaries().len();
+    for (name, starts) in [("STARTS", dfa.starts()), ("BOL_STARTS", dfa.bol_starts())] {
+        let items = starts.iter().map(|start| start.to_string());
+        writeln!(
+            code,
+            "const {}: [usize; {}] = [{}];",
+            name,
+            starts.len(),
+            items.collect::<Vec<_>>().join(", ")
+        )
+        .unwrap();
+    }
+    let eofs = dfa.eofs().iter().map(|eof| match eof {
+        Some(rule) => match configs.rules()[*rule].error() {
+            Some(error) => format!("Some(LexToken::EofError({:?}))", error),
+            None => format!(
+                "Some(LexToken::Token(Token::{}))",
+                configs.rules()[*rule].name()
+            ),
+        },
+        None => "None".to_string(),
+    });
+    writeln!(
+        code,
+        "const EOF: [Option<LexToken>; {}] = [{}];",
+        dfa.eofs().len(),
+        eofs.collect::<Vec<_>>().join(", ")
+    )
+    .unwrap();
+    writeln!(code, "const DEAD: usize = {};", Dfa::<usize>::DEAD).unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(code, "#[rustfmt::skip]").unwrap();
+    writeln!(code, "const BOUNDARIES: [char; {}] = [", width).unwrap();
+    write_list(code, dfa.boundaries().iter().map(|c| format!("{:?}", c)), 8);
+    writeln!(code, "];").unwrap();
+    writeln!(code).unwrap();
+
+    let transitions = dfa.transitions();
+    writeln!(code, "#[rustfmt::skip]").unwrap();
+    writeln!(
+        code,
+        "const TRANSITIONS: [{}; {}] = [",
+        state_type(dfa.len()),
+        transitions.len()
+    )
+    .unwrap();
+    for row in transitions.chunks(width) {
+        write_list(code, row.iter().map(|state| state.to_string()), usize::MAX);
+    }
+    writeln!(code, "];").unwrap();
+    writeln!(code).unwrap();
+
+    for (name, terminals) in [("ACCEPTS", dfa.terminals()), ("AT_END", dfa.at_ends())] {
+        let items = terminals
+            .iter()
+            .map(|terminal| token_item(configs, terminal));
+        writeln!(code, "#[rustfmt::skip]").unwrap();
+        writeln!(code, "const {}: [Option<Token>; {}] = [", name, dfa.len()).unwrap();
+        write_list(code, items, 4);
+        writeln!(code, "];").unwrap();
+        writeln!(code).unwrap();
+    }
+
+    let trailing = dfa.trailings().iter().map(|trailing| match trailing {
+        Some(TrailingContext::Head(len)) => format!("Some(Trailing::Head({}))", len),
+        Some(TrailingContext::Tail(len)) => format!("Some(Trailing::Tail({}))", len),
+        None => "None".to_string(),
+    });
+    writeln!(code, "#[rustfmt::skip]").unwrap();
+    writeln!(
+        code,
+        "const TRAILING: [Option<Trailing>; {}] = [",
+        dfa.len()
+    )
+    .unwrap();
+    write_list(code, trailing, 8);
+    writeln!(code, "];").unwrap();
+}
+
+const SCANNER: &str = r#"
+/// `r/s`のルールで、`r`と`s`のどちらの長さ (文字数) が決まっているか
+/// `r/s`のルールが無い仕様では使わない
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+enum Trailing {
+    Head(usize),
+    Tail(usize),
+}
+
+fn class_of(c: char) -> usize {
+    BOUNDARIES.partition_point(|&boundary| boundary <= c) - 1
+}
+
+/// `r/s`に一致した`matched`のうち、`r`の部分のバイト数
+fn token_len(matched: &str, trailing: Trailing) -> usize {
+    match trailing {
+        Trailing::Head(len) => matched
+            .char_indices()
+            .nth(len)
+            .map_or(matched.len(), |(offset, _)| offset),
+        Trailing::Tail(0) => matched.len(),
+        Trailing::Tail(len) => matched
+            .char_indices()
+            .nth_back(len - 1)
+            .map_or(0, |(offset, _)| offset),
+    }
+}
+
+/// `input`の先頭から最長一致するトークンと、そのバイト数を返す
+/// `input`は行頭から始まるものとして読む
+/// 同じ長さで一致するルールが複数あれば、仕様で先に書いたものを返す
+/// `r/s`のルールは`rs`で最長一致を決め、`r`の部分だけをトークンにする
+pub fn scan(input: &str) -> Option<(Token, usize)> {
+    scan_in(State::Initial, true, input)
+}
+
+/// スタート状態`start`で有効なルールだけで`scan`する
+/// `at_bol`が`true`なら`^`のルールも使う
+pub fn scan_in(start: State, at_bol: bool, input: &str) -> Option<(Token, usize)> {
+    let mut state = if at_bol {
+        BOL_STARTS[start as usize]
+    } else {
+        STARTS[start as usize]
+    };
+    let mut last = ACCEPTS[state].map(|token| (token, TRAILING[state], 0));
+    for (offset, c) in input.char_indices() {
+        state = TRANSITIONS[state * BOUNDARIES.len() + class_of(c)] as usize;
+        if state == DEAD {
+            break;
+        }
+        if let Some(token) = ACCEPTS[state] {
+            last = Some((token, TRAILING[state], offset + c.len_utf8()));
+        }
+    }
+    if let Some(token) = AT_END[state] {
+        last = Some((token, None, input.len()));
+    }
+    let (token, trailing, len) = last?;
+    match trailing {
+        Some(trailing) => Some((token, token_len(&input[..len], trailing))),
+        None => Some((token, len)),
+    }
+}
+
+/// 入力の中の範囲 (バイト単位)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexToken {
+    Token(Token),
+    /// どのルールにも一致しなかった
+    Error,
+    /// 入力の終わりで`<<EOF>>`のルールが報告したエラー
+    EofError(&'static str),
+}
+
+/// 最長一致でトークンを切り出し、(トークン, 範囲) を返す
+/// どのルールにも一致しなければ、一文字を`LexToken::Error`として返す
+/// 入力を読み終えたら、今のスタート状態の`EOF`を空の範囲で一度だけ返す
+pub struct Lexer<'a> {
+    input: &'a str,
+    position: usize,
+    state: State,
+    stack: Vec<State>,
+    finished: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            position: 0,
+            state: State::Initial,
+            stack: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// 次に読む位置 (バイト単位)
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 今のスタート状態
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// スタート状態を`state`に切り替える
+    pub fn begin(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// 今のスタート状態を積んでから`state`に切り替える
+    pub fn push_state(&mut self, state: State) {
+        self.stack.push(self.state);
+        self.state = state;
+    }
+
+    /// `push_state`の前のスタート状態に戻し、戻った状態を返す
+    /// 積んだ状態が無ければ何もせず`None`を返す
+    pub fn pop_state(&mut self) -> Option<State> {
+        self.state = self.stack.pop()?;
+        Some(self.state)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = (LexToken, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.position..];
+        let start = self.position;
+        let Some(c) = rest.chars().next() else {
+            if self.finished {
+                return None;
+            }
+            self.finished = true;
+            let token = EOF[self.state as usize]?;
+            return Some((token, Span { start, end: start }));
+        };
+        let at_bol = start == 0 || self.input[..start].ends_with('\n');
+        let (token, len) = match scan_in(self.state, at_bol, rest) {
+            Some((token, len)) if len > 0 => (LexToken::Token(token), len),
+            _ => (LexToken::Error, c.len_utf8()),
+        };
+        self.position += len;
+        Some((token, Span { start, end: self.position }))
+    }
+}
+"#;
+
+/// 字句解析器のコードを生成する
+pub fn generate(configs: &Configs) -> Result<String, ConfigError> {
+    let dfa = configs.dfa()?;
+    let mut code = "// flexで生成したコード 手で編集しないこと\n\n".to_string();
+    code.push_str(&configs.to_enum_code());
+    code.push('\n');
+    code.push_str(&configs.to_state_enum_code());
+    code.push('\n');
+    write_tables(&mut code, configs, &dfa);
+    code.push_str(SCANNER);
+    Ok(code)
+}
+
+#[cfg(test)]
+mod codegen_test {
+    use super::*;
+
+    #[test]
+    fn state_type_fits() {
+        assert_eq!(state_type(2), "u8");
+        assert_eq!(state_type(256), "u8");
+        assert_eq!(state_type(257), "u16");
+        assert_eq!(state_type(70000), "u32");
+    }
+
+    #[test]
+    fn tables_have_declared_length() {
+        let configs = Configs::from_path("./tests/test_toml_parse.toml").unwrap();
+        let code = generate(&configs).unwrap();
+        let dfa = configs.dfa().unwrap();
+        let boundaries = format!("BOUNDARIES: [char; {}]", dfa.boundaries().len());
+        let accepts = format!("ACCEPTS: [Option<Token>; {}]", dfa.len());
+        assert!(code.contains(&boundaries));
+        assert!(code.contains(&accepts));
+        assert!(code.contains("Some(Token::Manko)"));
+        assert!(code.contains("Some(Token::Tinko)"));
+    }
+}
@@ -0,0 +1,751 @@
+//! 字句解析器の仕様を書いたTOMLの読み込み
+//! ```toml
+//! [Manko]
+//! regex = "manko"
+//! ```
+//! のように、テーブル名をトークン名、`regex`をそのトークンの正規表現とする
+//! `case_insensitive = true`を書くと、そのルールは大文字小文字を区別しない
+//!
+//! `[definitions]`テーブルには名前付きの正規表現を書き、
+//! どのルールの`regex`からも`{DIGIT}`のように参照できる
+//! ```toml
+//! [definitions]
+//! DIGIT = "[0-9]"
+//!
+//! [Number]
+//! regex = "{DIGIT}+"
+//! ```
+//!
+//! `[states]`テーブルにはスタート状態 (字句解析器のモード) を書く
+//! 値が`"inclusive"`の状態では`states`を書いていないルールも有効で、
+//! `"exclusive"`の状態では`states`にその状態を書いたルールだけが有効
+//! 最初の状態は`Initial`で、`states = ["*"]`は全ての状態で有効
+//! ```toml
+//! [states]
+//! Comment = "exclusive"
+//!
+//! [CommentEnd]
+//! regex = '"*/"'
+//! states = ["Comment"]
+//! ```
+//!
+//! `regex = "<<EOF>>"`のルールは、入力の終わりで一度だけそのトークンを返す
+//! `states`を書けばその状態だけで有効で、書かなければ`<<EOF>>`のルールが無い全ての状態で有効
+//! `error`を書くと、トークンの代わりにそのメッセージのエラーを返す
+//! ```toml
+//! [Eof]
+//! regex = "<<EOF>>"
+//!
+//! [UnterminatedComment]
+//! regex = "<<EOF>>"
+//! states = ["Comment"]
+//! error = "unterminated block comment"
+//! ```
+//!
+//! 同じ長さで複数のルールに一致した時は、ファイルで先に書いたルールを優先する
+//! そのためtomlの`preserve_order`で、テーブルを書いた順のまま読む
+use std::error::Error;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use toml::value::Value;
+
+use crate::dfa::Dfa;
+use crate::nfa::Nfa;
+use crate::regex_ast::RegexAst;
+use crate::regex_error::RegexError;
+use crate::regex_parser::{parse_ast, parse_rule};
+use crate::regex_tokenizer::{Definitions, Regex};
+
+/// 定義を書くテーブルの名前 ルール名には使えない
+const DEFINITIONS: &str = "definitions";
+/// スタート状態を書くテーブルの名前 ルール名には使えない
+const STATES: &str = "states";
+/// 最初のスタート状態の名前
+pub const INITIAL: &str = "Initial";
+/// ルールの`states`に書くと、全てのスタート状態で有効になる
+const ALL_STATES: &str = "*";
+/// 入力の終わりのルールの`regex`
+pub const EOF: &str = "<<EOF>>";
+
+/// 仕様の読み込みやコンパイルで起きたエラー
+#[derive(Debug)]
+pub enum ConfigError {
+    /// ファイルが読めない
+    Io(PathBuf, io::Error),
+    /// TOMLとして読めない
+    Toml(toml::de::Error),
+    /// トップレベルの値がテーブルでない
+    NotTable(String),
+    /// `regex`が無い
+    MissingRegex(String),
+    /// `regex`が文字列でない
+    RegexNotString(String),
+    /// (ルール名, キー) の値が真偽値でない
+    NotBool(String, &'static str),
+    /// (ルール名, キー) の値が文字列でない
+    NotString(String, &'static str),
+    /// `<<EOF>>`でないルールに`error`がある
+    ErrorNotEof(String),
+    /// `[definitions]`の値が文字列でない
+    DefinitionNotString(String),
+    /// `[states]`の値が`"inclusive"`か`"exclusive"`でない
+    InvalidStateKind(String),
+    /// 同じ名前のスタート状態がある
+    DuplicateState(String),
+    /// ルールの`states`が文字列の配列でない
+    StatesNotArray(String),
+    /// (ルール名, 状態名) の状態が`[states]`に無い
+    UnknownState(String, String),
+    /// トークン名がRustの識別子として使えない
+    InvalidName(String),
+    /// 正規表現のエラー
+    Regex(String, RegexError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "cannot read {:?}: {}", path, error),
+            Self::Toml(error) => write!(f, "invalid toml: {}", error),
+            Self::NotTable(name) => write!(f, "`{}` must be a table", name),
+            Self::MissingRegex(name) => write!(f, "`{}` has no `regex`", name),
+            Self::RegexNotString(name) => write!(f, "`{}.regex` must be a string", name),
+            Self::NotBool(name, key) => write!(f, "`{}.{}` must be a boolean", name, key),
+            Self::NotString(name, key) => write!(f, "`{}.{}` must be a string", name, key),
+            Self::ErrorNotEof(name) => {
+                write!(f, "`{}.error` is only allowed on `{}` rules", name, EOF)
+            }
+            Self::DefinitionNotString(name) => {
+                write!(f, "`{}.{}` must be a string", DEFINITIONS, name)
+            }
+            Self::InvalidStateKind(name) => write!(
+                f,
+                "`{}.{}` must be \"inclusive\" or \"exclusive\"",
+                STATES, name
+            ),
+            Self::DuplicateState(name) => write!(f, "start state `{}` is already defined", name),
+            Self::StatesNotArray(name) => {
+                write!(f, "`{}.{}` must be an array of state names", name, STATES)
+            }
+            Self::UnknownState(name, state) => {
+                write!(f, "`{}` refers to undefined start state `{}`", name, state)
+            }
+            Self::InvalidName(name) => write!(f, "`{}` is not a valid token name", name),
+            Self::Regex(name, error) => write!(f, "in `{}`:\n{}", name, error),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// スタート状態の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateKind {
+    /// `states`を書いていないルールも有効
+    Inclusive,
+    /// `states`にこの状態を書いたルールだけが有効
+    Exclusive,
+}
+
+/// スタート状態 (字句解析器のモード) 一つ分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartState {
+    name: String,
+    kind: StateKind,
+}
+
+impl StartState {
+    pub fn new(name: String, kind: StateKind) -> Self {
+        Self { name, kind }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> StateKind {
+        self.kind
+    }
+}
+
+/// トークン一つ分のルール
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    name: String,
+    regex: String,
+    case_insensitive: bool,
+    states: Vec<String>,
+    error: Option<String>,
+}
+
+impl Rule {
+    pub fn new(name: String, regex: String) -> Self {
+        Self {
+            name,
+            regex,
+            case_insensitive: false,
+            states: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// 入力の終わりのルールか
+    pub fn is_eof(&self) -> bool {
+        self.regex == EOF
+    }
+
+    /// 入力の終わりで、トークンの代わりに返すエラー
+    pub fn set_error(&mut self, error: Option<String>) {
+        self.error = error;
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// このルールが有効なスタート状態 空なら全てのinclusiveな状態
+    pub fn set_states(&mut self, states: Vec<String>) {
+        self.states = states;
+    }
+
+    pub fn states(&self) -> &[String] {
+        &self.states
+    }
+
+    /// スタート状態`state`でこのルールが有効か
+    pub fn is_active(&self, state: &StartState) -> bool {
+        if self.states.is_empty() {
+            state.kind == StateKind::Inclusive
+        } else {
+            self.states
+                .iter()
+                .any(|name| name == ALL_STATES || *name == state.name)
+        }
+    }
+
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn regex(&self) -> &str {
+        &self.regex
+    }
+}
+
+/// 仕様全体 ルールの並び順がそのまま優先度になる
+/// スタート状態は`Initial`が先頭で、その後に`[states]`に書いた順に並ぶ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Configs {
+    inner: Vec<Rule>,
+    definitions: Definitions,
+    states: Vec<StartState>,
+}
+
+fn initial_state() -> StartState {
+    StartState::new(INITIAL.to_string(), StateKind::Inclusive)
+}
+
+/// Rustのキーワードと予約語 識別子には使えない
+const KEYWORDS: &[&str] = &[
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Rustの識別子 (enumのバリアント名) として使えるか
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let head = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    head && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "_"
+        && !KEYWORDS.contains(&name)
+}
+
+impl Configs {
+    pub fn new(inner: Vec<Rule>) -> Self {
+        Self {
+            inner,
+            definitions: Definitions::new(),
+            states: vec![initial_state()],
+        }
+    }
+
+    /// スタート状態を追加する
+    pub fn add_state(&mut self, state: StartState) -> Result<(), ConfigError> {
+        if self.states.iter().any(|x| x.name == state.name) {
+            return Err(ConfigError::DuplicateState(state.name));
+        }
+        self.states.push(state);
+        Ok(())
+    }
+
+    pub fn states(&self) -> &[StartState] {
+        &self.states
+    }
+
+    pub fn set_definitions(&mut self, definitions: Definitions) {
+        self.definitions = definitions;
+    }
+
+    pub fn definitions(&self) -> &Definitions {
+        &self.definitions
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let string = read_to_string(path.as_ref())
+            .map_err(|error| ConfigError::Io(path.as_ref().to_path_buf(), error))?;
+        Self::from_toml(&string)
+    }
+
+    pub fn from_toml(string: &str) -> Result<Self, ConfigError> {
+        let table = match string.parse::<Value>().map_err(ConfigError::Toml)? {
+            Value::Table(table) => table,
+            // 文字列全体をパースするとテーブルにしかならない
+            _ => unreachable!(),
+        };
+
+        let mut configs = Self::new(Vec::new());
+        for (name, value) in table {
+            let value = match value.as_table() {
+                Some(value) => value,
+                None => return Err(ConfigError::NotTable(name)),
+            };
+            if name == DEFINITIONS {
+                for (name, regex) in value {
+                    let regex = match regex {
+                        Value::String(regex) => regex.clone(),
+                        _ => return Err(ConfigError::DefinitionNotString(name.clone())),
+                    };
+                    if !is_identifier(name) {
+                        return Err(ConfigError::InvalidName(name.clone()));
+                    }
+                    configs.definitions.insert(name.clone(), regex);
+                }
+                continue;
+            }
+            if name == STATES {
+                for (name, kind) in value {
+                    let kind = match kind.as_str() {
+                        Some("inclusive") => StateKind::Inclusive,
+                        Some("exclusive") => StateKind::Exclusive,
+                        _ => return Err(ConfigError::InvalidStateKind(name.clone())),
+                    };
+                    if !is_identifier(name) {
+                        return Err(ConfigError::InvalidName(name.clone()));
+                    }
+                    configs.add_state(StartState::new(name.clone(), kind))?;
+                }
+                continue;
+            }
+            let regex = match value.get("regex") {
+                Some(Value::String(regex)) => regex.clone(),
+                Some(_) => return Err(ConfigError::RegexNotString(name)),
+                None => return Err(ConfigError::MissingRegex(name)),
+            };
+            let case_insensitive = match value.get("case_insensitive") {
+                Some(Value::Boolean(flag)) => *flag,
+                Some(_) => return Err(ConfigError::NotBool(name, "case_insensitive")),
+                None => false,
+            };
+            let states = match value.get(STATES) {
+                Some(Value::Array(states)) => states
+                    .iter()
+                    .map(|state| state.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>(),
+                Some(_) => None,
+                None => Some(Vec::new()),
+            };
+            let Some(states) = states else {
+                return Err(ConfigError::StatesNotArray(name));
+            };
+            let error = match value.get("error") {
+                Some(Value::String(_)) if regex != EOF => {
+                    return Err(ConfigError::ErrorNotEof(name));
+                }
+                Some(Value::String(error)) => Some(error.clone()),
+                Some(_) => return Err(ConfigError::NotString(name, "error")),
+                None => None,
+            };
+            if !is_identifier(&name) {
+                return Err(ConfigError::InvalidName(name));
+            }
+            let mut rule = Rule::new(name, regex);
+            rule.set_case_insensitive(case_insensitive);
+            rule.set_states(states);
+            rule.set_error(error);
+            configs.inner.push(rule);
+        }
+
+        // `[states]`はルールより後に書いてもよいので、最後にまとめて確かめる
+        for rule in &configs.inner {
+            let unknown = rule.states.iter().find(|name| {
+                *name != ALL_STATES && configs.states.iter().all(|state| state.name != **name)
+            });
+            if let Some(state) = unknown {
+                return Err(ConfigError::UnknownState(rule.name.clone(), state.clone()));
+            }
+        }
+        Ok(configs)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.inner
+    }
+
+    /// スタート状態ごとに、入力の終わりで使うルールの番号
+    /// `states`を書いたルールを優先し、無ければ`states`を書いていないルールを使う
+    pub fn eof_rules(&self) -> Vec<Option<usize>> {
+        let eof_rules = self
+            .inner
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.is_eof())
+            .collect::<Vec<_>>();
+        self.states
+            .iter()
+            .map(|state| {
+                let scoped = eof_rules
+                    .iter()
+                    .find(|(_, rule)| !rule.states.is_empty() && rule.is_active(state));
+                let unscoped = || eof_rules.iter().find(|(_, rule)| rule.states.is_empty());
+                scoped.or_else(unscoped).map(|(idx, _)| *idx)
+            })
+            .collect()
+    }
+
+    /// `rule`の正規表現 `{NAME}`は`definitions`から引く
+    fn regex(&self, rule: &Rule, definitions: &Rc<Definitions>) -> Result<Regex, ConfigError> {
+        let mut regex = Regex::new(rule.regex.clone())
+            .map_err(|error| ConfigError::Regex(rule.name.clone(), error))?;
+        regex.set_case_insensitive(rule.case_insensitive);
+        regex.set_definitions(Rc::clone(definitions));
+        Ok(regex)
+    }
+
+    /// `rule`の構文木 `{NAME}`の展開と`case_insensitive`も反映する
+    pub fn ast(&self, rule: &Rule) -> Result<RegexAst, ConfigError> {
+        let regex = self.regex(rule, &Rc::new(self.definitions.clone()))?;
+        parse_ast(&regex).map_err(|error| ConfigError::Regex(rule.name.clone(), error))
+    }
+
+    /// 全てのルールを一つのNFAにまとめる terminalはルールの番号
+    /// NFAの開始状態は`states`と同じ順に並ぶ
+    pub fn nfa(&self) -> Result<Nfa<usize>, ConfigError> {
+        let mut nfa = Nfa::new();
+        for _ in 1..self.states.len() {
+            nfa.add_start();
+        }
+        for (state, rule) in self.eof_rules().into_iter().enumerate() {
+            if let Some(rule) = rule {
+                nfa.set_eof(state, rule);
+            }
+        }
+        let definitions = Rc::new(self.definitions.clone());
+        for (idx, rule) in self.inner.iter().enumerate() {
+            if rule.is_eof() {
+                continue;
+            }
+            let regex = self.regex(rule, &definitions)?;
+            let starts = (0..self.states.len())
+                .filter(|&state| rule.is_active(&self.states[state]))
+                .collect::<Vec<_>>();
+            parse_rule(&mut nfa, &regex, idx, &starts)
+                .map_err(|error| ConfigError::Regex(rule.name.clone(), error))?;
+        }
+        Ok(nfa)
+    }
+
+    /// 全てのルールをまとめた最小DFA
+    pub fn dfa(&self) -> Result<Dfa<usize>, ConfigError> {
+        Ok(Dfa::from_nfa(&self.nfa()?).minimize())
+    }
+
+    /// トークンのenum `error`を書いたルールはトークンにならないので含めない
+    pub fn to_enum_code(&self) -> String {
+        let mut code = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n".to_string();
+        code.push_str("pub enum Token {\n");
+        let tokens = self.inner.iter().filter(|rule| rule.error.is_none());
+        tokens.fold(&mut code, |prev, x| {
+            prev.push_str(&format!("    {},\n", x.name()));
+            prev
+        });
+        code.push_str("}\n");
+        code
+    }
+
+    /// スタート状態のenum バリアントの順は`states`と同じ
+    pub fn to_state_enum_code(&self) -> String {
+        let mut code = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n".to_string();
+        code.push_str("pub enum State {\n");
+        for state in &self.states {
+            code.push_str(&format!("    {},\n", state.name()));
+        }
+        code.push_str("}\n");
+        code
+    }
+}
+
+#[cfg(test)]
+mod config_test {
+    use super::*;
+
+    #[test]
+    fn parse_toml() {
+        let configs = Configs::from_path("./tests/test_toml_parse.toml").unwrap();
+        let ans = Configs::new(vec![
+            Rule::new("Manko".to_string(), "manko".to_string()),
+            Rule::new("Tinko".to_string(), "tinko".to_string()),
+        ]);
+        assert_eq!(configs, ans);
+    }
+
+    #[test]
+    fn keep_file_order() {
+        let configs = Configs::from_toml(
+            "[Tinko]\nregex = \"tinko\"\n[Ident]\nregex = \"[a-z]+\"\n[Manko]\nregex = \"manko\"",
+        )
+        .unwrap();
+        let names = configs.rules().iter().map(Rule::name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Tinko", "Ident", "Manko"]);
+    }
+
+    #[test]
+    fn first_rule_wins() {
+        let configs =
+            Configs::from_toml("[Ident]\nregex = \"[a-z]+\"\n[If]\nregex = \"if\"").unwrap();
+        let dfa = configs.dfa().unwrap();
+        assert_eq!(dfa.collect_terminal(&['i', 'f'], 0), vec![(0, 1), (0, 2)]);
+
+        let configs =
+            Configs::from_toml("[If]\nregex = \"if\"\n[Ident]\nregex = \"[a-z]+\"").unwrap();
+        let dfa = configs.dfa().unwrap();
+        assert_eq!(dfa.collect_terminal(&['i', 'f'], 0), vec![(1, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn enum_code() {
+        let code = Configs::from_path("./tests/test_toml_parse.toml")
+            .unwrap()
+            .to_enum_code();
+        let ans = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n\
+                   pub enum Token {\n    Manko,\n    Tinko,\n}\n";
+        assert_eq!(code, ans);
+    }
+
+    macro_rules! config_error {
+        ($fn_name:ident, $toml:expr, $pattern:pat) => {
+            #[test]
+            fn $fn_name() {
+                let error = Configs::from_toml($toml).err().unwrap();
+                assert!(matches!(error, $pattern), "{:?}", error);
+            }
+        };
+    }
+
+    config_error!(not_table, "A = 1", ConfigError::NotTable(_));
+    config_error!(
+        missing_regex,
+        "[A]\nre = \"a\"",
+        ConfigError::MissingRegex(_)
+    );
+    config_error!(
+        regex_not_string,
+        "[A]\nregex = 1",
+        ConfigError::RegexNotString(_)
+    );
+    config_error!(
+        invalid_name,
+        "[1A]\nregex = \"a\"",
+        ConfigError::InvalidName(_)
+    );
+    config_error!(
+        keyword_name,
+        "[if]\nregex = \"if\"",
+        ConfigError::InvalidName(_)
+    );
+    config_error!(
+        keyword_state,
+        "[states]\ntype = \"inclusive\"",
+        ConfigError::InvalidName(_)
+    );
+    config_error!(invalid_toml, "[A", ConfigError::Toml(_));
+    config_error!(
+        case_insensitive_not_bool,
+        "[A]\nregex = \"a\"\ncase_insensitive = \"yes\"",
+        ConfigError::NotBool(_, "case_insensitive")
+    );
+
+    #[test]
+    fn case_insensitive() {
+        let configs = Configs::from_toml(
+            "[Select]\nregex = \"select\"\ncase_insensitive = true\n[Ident]\nregex = \"[a-z]+\"",
+        )
+        .unwrap();
+        assert!(configs.rules()[0].case_insensitive());
+        assert!(!configs.rules()[1].case_insensitive());
+        let dfa = configs.dfa().unwrap();
+        let query = "SeLeCt".chars().collect::<Vec<_>>();
+        assert_eq!(dfa.collect_terminal(&query, 0).last(), Some(&(0, 6)));
+        let query = "Sel".chars().collect::<Vec<_>>();
+        assert!(dfa.collect_terminal(&query, 0).is_empty());
+    }
+
+    config_error!(
+        definition_not_string,
+        "[definitions]\nDIGIT = 1",
+        ConfigError::DefinitionNotString(_)
+    );
+    config_error!(
+        definition_name,
+        "[definitions]\n\"1D\" = \"a\"",
+        ConfigError::InvalidName(_)
+    );
+
+    #[test]
+    fn definitions() {
+        let configs = Configs::from_toml(
+            "[definitions]\nDIGIT = \"[0-9]\"\nNUM = \"{DIGIT}+\"\n\
+             [Float]\nregex = \"{NUM}\\\\.{NUM}\"\n[Int]\nregex = \"{NUM}\"",
+        )
+        .unwrap();
+        assert_eq!(configs.definitions().get("DIGIT"), Some("[0-9]"));
+        let names = configs.rules().iter().map(Rule::name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Float", "Int"]);
+        let dfa = configs.dfa().unwrap();
+        let query = "12.5".chars().collect::<Vec<_>>();
+        assert_eq!(
+            dfa.collect_terminal(&query, 0),
+            vec![(1, 1), (1, 2), (0, 4)]
+        );
+    }
+
+    #[test]
+    fn undefined_name() {
+        let configs = Configs::from_toml("[A]\nregex = \"{DIGIT}\"").unwrap();
+        let error = configs.dfa().err().unwrap();
+        assert!(matches!(error, ConfigError::Regex(ref name, _) if name == "A"));
+    }
+
+    config_error!(
+        invalid_state_kind,
+        "[states]\nStr = \"both\"",
+        ConfigError::InvalidStateKind(_)
+    );
+    config_error!(
+        duplicate_state,
+        "[states]\nInitial = \"inclusive\"",
+        ConfigError::DuplicateState(_)
+    );
+    config_error!(
+        states_not_array,
+        "[A]\nregex = \"a\"\nstates = \"Str\"",
+        ConfigError::StatesNotArray(_)
+    );
+    config_error!(
+        unknown_state,
+        "[A]\nregex = \"a\"\nstates = [\"Str\"]\n[states]\nCode = \"exclusive\"",
+        ConfigError::UnknownState(_, _)
+    );
+
+    #[test]
+    fn start_states() {
+        let configs = Configs::from_toml(
+            "[A]\nregex = \"a\"\n[B]\nregex = \"b\"\nstates = [\"Str\"]\n\
+             [C]\nregex = \"c\"\nstates = [\"*\"]\n[D]\nregex = \"d\"\nstates = [\"Initial\", \"Str\"]\n\
+             [states]\nStr = \"exclusive\"\nCode = \"inclusive\"",
+        )
+        .unwrap();
+        let names = configs
+            .states()
+            .iter()
+            .map(StartState::name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Initial", "Str", "Code"]);
+        let active = |state: &StartState| {
+            configs
+                .rules()
+                .iter()
+                .filter(|rule| rule.is_active(state))
+                .map(Rule::name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(active(&configs.states()[0]), vec!["A", "C", "D"]);
+        assert_eq!(active(&configs.states()[1]), vec!["B", "C", "D"]);
+        assert_eq!(active(&configs.states()[2]), vec!["A", "C"]);
+
+        let dfa = configs.dfa().unwrap();
+        assert_eq!(dfa.starts().len(), 3);
+        let accepts = |state: usize, input: &str| {
+            dfa.longest_match_from(dfa.starts()[state], input)
+                .map(|(rule, _)| *rule)
+        };
+        assert_eq!(accepts(0, "a"), Some(0));
+        assert_eq!(accepts(0, "b"), None);
+        assert_eq!(accepts(1, "a"), None);
+        assert_eq!(accepts(1, "b"), Some(1));
+        assert_eq!(accepts(2, "d"), None);
+    }
+
+    #[test]
+    fn state_enum_code() {
+        let configs =
+            Configs::from_toml("[states]\nStr = \"exclusive\"\n[A]\nregex = \"a\"").unwrap();
+        let ans = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n\
+                   pub enum State {\n    Initial,\n    Str,\n}\n";
+        assert_eq!(configs.to_state_enum_code(), ans);
+    }
+
+    config_error!(
+        error_not_eof,
+        "[A]\nregex = \"a\"\nerror = \"oops\"",
+        ConfigError::ErrorNotEof(_)
+    );
+    config_error!(
+        error_not_string,
+        "[A]\nregex = \"<<EOF>>\"\nerror = 1",
+        ConfigError::NotString(_, "error")
+    );
+
+    #[test]
+    fn eof_rules() {
+        let configs = Configs::from_toml(
+            "[states]\nStr = \"exclusive\"\nCode = \"inclusive\"\n[A]\nregex = \"a\"\n\
+             [Eof]\nregex = \"<<EOF>>\"\n[StrEof]\nregex = \"<<EOF>>\"\nstates = [\"Str\"]\n\
+             error = \"unterminated string\"",
+        )
+        .unwrap();
+        assert!(configs.rules()[1].is_eof());
+        assert_eq!(configs.rules()[2].error(), Some("unterminated string"));
+        assert_eq!(configs.eof_rules(), vec![Some(1), Some(2), Some(1)]);
+        let dfa = configs.dfa().unwrap();
+        assert_eq!(dfa.eofs(), &[Some(1), Some(2), Some(1)]);
+        // `<<EOF>>`は正規表現としては使わない
+        assert!(dfa.longest_match("<<EOF>>").is_none());
+        let ans = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n\
+                   pub enum Token {\n    A,\n    Eof,\n}\n";
+        assert_eq!(configs.to_enum_code(), ans);
+    }
+
+    #[test]
+    fn regex_error() {
+        let configs = Configs::from_toml("[A]\nregex = \"a(\"").unwrap();
+        let error = configs.dfa().err().unwrap();
+        assert!(matches!(error, ConfigError::Regex(ref name, _) if name == "A"));
+    }
+}
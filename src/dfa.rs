@@ -1,58 +1,608 @@
-// #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
-// pub enum DfaItem<C, T> {
-//     Content(C),
-//     Terminal(T)
-// }
-//
-// impl<C, T> DfaItem<C, T> {
-//     fn is_terminal(&self) -> bool {
-//         matches!(self, DfaItem::Terminal(_))
-//     }
-//
-//     fn from_content(content: C) -> Self {
-//         DfaItem::Content(content)
-//     }
-// }
-//
-// #[derive(Debug, Clone, PartialEq, Eq)]
-// pub struct DfaNode<C, T> {
-//     directions: Vec<(DfaItem<C, T>, usize)>,
-// }
-//
-// impl<C, T: Clone> DfaNode<C, T> {
-//     fn new() -> Self {
-//         Self {
-//             directions: Vec::new(),
-//         }
-//     }
-//     fn add_direction(&mut self, dir_char: DfaItem<C, T>, idx: usize) {
-//         self.directions.push((dir_char, idx));
-//     }
-//
-//     pub fn add_content(&mut self, content: C, idx: usize) {
-//         let item = DfaItem::Content(content);
-//         self.add_direction(item, idx);
-//     }
-//
-//     pub fn add_terminal(&mut self, terminal: T) {
-//         let item = DfaItem::Terminal(terminal);
-//         self.add_direction(item, 0);
-//     }
-//
-//     fn increment_all_index(&mut self, inc: usize) {
-//         self.directions.iter_mut().for_each(|(_, idx)| *idx += inc);
-//     }
-//
-//     fn from_content(content: C) -> Self {
-//         let item = DfaItem::from_content(content);
-//         DfaNode {
-//             directions: vec![(item, 1)],
-//         }
-//     }
-// }
-//
-// #[derive(Clone, PartialEq, Eq, Debug)]
-// pub struct Dfa<C, T> {
-//     nfa: Vec<DfaNode<C, T>>,
-//     len: usize,
-// }
+//! DFAに関する実装
+//! NFAから部分集合構成法でDFAを作る
+//!
+//! 遷移は (状態数 × 文字の区間の数) の表で持つ
+//! 文字全体を、NFAの辺に現れる範囲の境界で区間に分けておくと、
+//! 同じ区間の文字はどの状態からも必ず同じ遷移をする
+//!
+//! `Dfa::minimize`はHopcroftの分割改良で同値な状態をまとめる
+//!
+//! `r/s`のルールの受理状態は`TrailingContext`も持ち、
+//! `longest_match`は`rs`全体で最長一致を決めてから`r`の長さだけを返す
+//!
+//! `^`のルールのため、開始状態は行頭用 (`bol_starts`) とそれ以外の二組ある
+//! `r$`の`r`の終わりは、入力を最後まで読んだ時だけ`at_end`で受理する
+//! `<<EOF>>`のルールは遷移に関係しないので、開始状態ごとに`eofs`で持つ
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::char_class::next_char;
+use crate::nfa::{Nfa, StateId, TrailingContext};
+
+/// `Dfa`の中の状態を指すインデックス
+pub type DfaStateId = usize;
+
+/// 表で遷移を持つDFA
+/// 状態0は死状態で、どの文字を読んでも自分自身に遷移し、受理もしない
+/// NFAの開始状態それぞれに対応する開始状態を持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dfa<T> {
+    /// i番目の区間の先頭の文字 昇順で、先頭は必ず'\0'
+    boundaries: Vec<char>,
+    /// `state * boundaries.len() + class`番目に遷移先を持つ
+    transitions: Vec<DfaStateId>,
+    /// 受理状態ならそのterminal
+    terminals: Vec<Option<T>>,
+    /// `r/s`のルールの受理状態なら、`r`と`s`の区切り方
+    trailing: Vec<Option<TrailingContext>>,
+    /// 入力を最後まで読んでここにいる時に、`terminals`の代わりに受理するterminal
+    at_end: Vec<Option<T>>,
+    starts: Vec<DfaStateId>,
+    bol_starts: Vec<DfaStateId>,
+    /// `starts`と同じ順の、入力の終わりに返すterminal
+    eofs: Vec<Option<T>>,
+}
+
+impl<T> Dfa<T> {
+    /// 死状態
+    pub const DEAD: DfaStateId = 0;
+
+    /// NFAの`start`に対応する開始状態
+    pub fn start(&self) -> DfaStateId {
+        self.starts[0]
+    }
+
+    /// NFAの`starts`と同じ順の開始状態
+    pub fn starts(&self) -> &[DfaStateId] {
+        &self.starts
+    }
+
+    /// `starts`と同じ順の、行頭で使う開始状態
+    pub fn bol_starts(&self) -> &[DfaStateId] {
+        &self.bol_starts
+    }
+
+    /// `idx`番目の開始状態で、入力の終わりに返すterminal
+    pub fn eof(&self, idx: usize) -> Option<&T> {
+        self.eofs[idx].as_ref()
+    }
+
+    pub fn eofs(&self) -> &[Option<T>] {
+        &self.eofs
+    }
+
+    /// 状態数 (死状態も含む)
+    pub fn len(&self) -> usize {
+        self.terminals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terminals.is_empty()
+    }
+
+    pub fn is_dead(&self, state: DfaStateId) -> bool {
+        state == Self::DEAD
+    }
+
+    /// 各区間の先頭の文字
+    pub fn boundaries(&self) -> &[char] {
+        &self.boundaries
+    }
+
+    /// 遷移表 (行が状態、列が区間)
+    pub fn transitions(&self) -> &[DfaStateId] {
+        &self.transitions
+    }
+
+    /// `c`の属する区間の番号
+    pub fn class_of(&self, c: char) -> usize {
+        self.boundaries.partition_point(|&boundary| boundary <= c) - 1
+    }
+
+    pub fn next(&self, state: DfaStateId, c: char) -> DfaStateId {
+        self.transitions[state * self.boundaries.len() + self.class_of(c)]
+    }
+
+    pub fn terminal(&self, state: DfaStateId) -> Option<&T> {
+        self.terminals[state].as_ref()
+    }
+
+    pub fn terminals(&self) -> &[Option<T>] {
+        &self.terminals
+    }
+
+    pub fn trailing(&self, state: DfaStateId) -> Option<TrailingContext> {
+        self.trailing[state]
+    }
+
+    /// 各状態の`r/s`の区切り方
+    pub fn trailings(&self) -> &[Option<TrailingContext>] {
+        &self.trailing
+    }
+
+    pub fn at_end(&self, state: DfaStateId) -> Option<&T> {
+        self.at_end[state].as_ref()
+    }
+
+    /// 各状態の入力の終わりで受理するterminal
+    pub fn at_ends(&self) -> &[Option<T>] {
+        &self.at_end
+    }
+
+    /// `input`の先頭から最長一致するterminalと、トークンにするバイト数を返す
+    /// `input`は行頭から始まるものとして読む
+    /// 同じ長さなら、構成時に優先度の高いルールが選ばれている
+    /// `r/s`のルールなら、`rs`で最長一致を決めて`r`の部分の長さを返す
+    pub fn longest_match(&self, input: &str) -> Option<(&T, usize)> {
+        self.longest_match_from(self.bol_starts[0], input)
+    }
+
+    /// `longest_match`を開始状態`start`から行う
+    pub fn longest_match_from(&self, start: DfaStateId, input: &str) -> Option<(&T, usize)> {
+        let mut state = start;
+        let mut last = self
+            .terminal(state)
+            .map(|terminal| (terminal, self.trailing(state), 0));
+        for (offset, c) in input.char_indices() {
+            state = self.next(state, c);
+            if self.is_dead(state) {
+                break;
+            }
+            if let Some(terminal) = self.terminal(state) {
+                last = Some((terminal, self.trailing(state), offset + c.len_utf8()));
+            }
+        }
+        if let Some(terminal) = self.at_end(state) {
+            last = Some((terminal, None, input.len()));
+        }
+        let (terminal, trailing, len) = last?;
+        match trailing {
+            Some(trailing) => Some((terminal, trailing.token_len(&input[..len]))),
+            None => Some((terminal, len)),
+        }
+    }
+}
+
+/// NFAの辺に現れる範囲の境界を集め、文字全体を区間に分ける
+fn boundaries<T>(nfa: &Nfa<T>) -> Vec<char> {
+    let mut boundaries = vec!['\0'];
+    let classes = nfa
+        .states()
+        .flat_map(|(_, node)| node.edges())
+        .filter_map(|(edge, _)| edge.char_class());
+    for class in classes {
+        for &(start, end) in class.ranges() {
+            boundaries.push(start);
+            boundaries.extend(next_char(end));
+        }
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    boundaries
+}
+
+impl<T: Clone> Dfa<T> {
+    /// 部分集合構成法でNFAをDFAにする
+    /// DFAの受理状態には、含まれるNFAの受理状態のうち最も優先度の高いterminalを持たせる
+    pub fn from_nfa(nfa: &Nfa<T>) -> Self {
+        let boundaries = boundaries(nfa);
+        let class_of = |c: char| boundaries.partition_point(|&boundary| boundary <= c) - 1;
+        // 各状態の各辺が、どの区間の文字で遷移できるか
+        let edge_classes = nfa
+            .states()
+            .map(|(_, node)| {
+                node.edges()
+                    .iter()
+                    .filter_map(|(edge, child)| Some((edge.char_class()?, *child)))
+                    .map(|(class, child)| {
+                        let classes = class
+                            .ranges()
+                            .iter()
+                            .flat_map(|&(start, end)| class_of(start)..=class_of(end))
+                            .collect::<Vec<_>>();
+                        (classes, child)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let width = boundaries.len();
+        let mut dfa = Dfa {
+            boundaries: Vec::new(),
+            transitions: vec![Self::DEAD; width],
+            terminals: vec![None],
+            trailing: vec![None],
+            at_end: vec![None],
+            starts: Vec::new(),
+            bol_starts: Vec::new(),
+            eofs: nfa.eofs().to_vec(),
+        };
+        let mut ids = HashMap::new();
+        ids.insert(Vec::new(), Self::DEAD);
+        let mut sets = vec![Vec::new()];
+
+        let mut starts = Vec::new();
+        for start in nfa.starts().iter().copied().chain(nfa.bol_starts()) {
+            let set = closure(nfa, [start]);
+            let id = match ids.get(&set) {
+                Some(&id) => id,
+                None => {
+                    let id = dfa.add_state(nfa, &set, width);
+                    ids.insert(set.clone(), id);
+                    sets.push(set);
+                    id
+                }
+            };
+            starts.push(id);
+        }
+        dfa.bol_starts = starts.split_off(nfa.starts().len());
+        dfa.starts = starts;
+
+        let mut current = 1;
+        while current < sets.len() {
+            // 区間ごとの遷移先を集め、遷移先が同じ区間はまとめてε閉包を取る
+            // 状態の番号が毎回同じになるよう、BTreeMapで順序を固定する
+            let mut targets = vec![Vec::new(); width];
+            for id in &sets[current] {
+                for (classes, child) in &edge_classes[id.index()] {
+                    classes
+                        .iter()
+                        .for_each(|&class| targets[class].push(*child));
+                }
+            }
+            let mut grouped: BTreeMap<Vec<StateId>, Vec<usize>> = BTreeMap::new();
+            for (class, mut target) in targets.into_iter().enumerate() {
+                if target.is_empty() {
+                    continue;
+                }
+                target.sort_unstable();
+                target.dedup();
+                grouped.entry(target).or_default().push(class);
+            }
+
+            for (target, classes) in grouped {
+                let set = closure(nfa, target);
+                let next = match ids.get(&set) {
+                    Some(&next) => next,
+                    None => {
+                        let next = dfa.add_state(nfa, &set, width);
+                        ids.insert(set.clone(), next);
+                        sets.push(set);
+                        next
+                    }
+                };
+                for class in classes {
+                    dfa.transitions[current * width + class] = next;
+                }
+            }
+            current += 1;
+        }
+
+        dfa.boundaries = boundaries;
+        dfa
+    }
+
+    /// NFAの状態集合`set`に対応する状態を、遷移先が全て死状態の行として追加する
+    /// 入力の終わりでだけ受理する状態も含めて一番優先度が高いのがそれなら、`at_end`に持たせる
+    fn add_state(&mut self, nfa: &Nfa<T>, set: &[StateId], width: usize) -> DfaStateId {
+        let winner = |at_end: bool| {
+            set.iter()
+                .filter(|id| at_end || !nfa[**id].eof_only())
+                .filter_map(|id| Some((nfa[*id].priority()?, &nfa[*id])))
+                .min_by_key(|(priority, _)| *priority)
+                .map(|(_, node)| node)
+        };
+        let at_end = winner(true).filter(|node| node.eof_only());
+        let winner = winner(false);
+        self.terminals
+            .push(winner.and_then(|node| node.terminal().cloned()));
+        self.trailing.push(winner.and_then(|node| node.trailing()));
+        self.at_end
+            .push(at_end.and_then(|node| node.terminal().cloned()));
+        self.transitions
+            .extend(std::iter::repeat_n(Self::DEAD, width));
+        self.terminals.len() - 1
+    }
+
+    /// 開始状態から`query[idx..]`を読み、受理状態に着く度に
+    /// (terminal, 読み終わった位置) を返す 死状態に落ちたらそこで止める
+    /// `idx`が行頭なら行頭の開始状態から読む
+    pub fn collect_terminal(&self, query: &[char], idx: usize) -> Vec<(T, usize)> {
+        let mut res = Vec::new();
+        let mut state = if idx == 0 || query[idx - 1] == '\n' {
+            self.bol_starts[0]
+        } else {
+            self.start()
+        };
+        let mut position = idx;
+        loop {
+            let terminal = if position == query.len() {
+                self.at_end(state).or(self.terminal(state))
+            } else {
+                self.terminal(state)
+            };
+            if let Some(terminal) = terminal {
+                res.push((terminal.clone(), position));
+            }
+            if position == query.len() || self.is_dead(state) {
+                return res;
+            }
+            state = self.next(state, query[position]);
+            position += 1;
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> Dfa<T> {
+    /// Hopcroftの分割改良で同値な状態をまとめたDFAを返す
+    /// 受理するterminalか`r/s`の区切り方、入力の終わりで受理するterminalが違う状態は、
+    /// 最初から別のブロックに分けておく
+    /// 最後に、全ての状態で遷移先が同じになった隣り合う区間もまとめる
+    pub fn minimize(&self) -> Self {
+        let len = self.len();
+        let width = self.boundaries.len();
+        // inverse[target * width + class] = classの文字でtargetに遷移する状態
+        let mut inverse = vec![Vec::new(); len * width];
+        for state in 0..len {
+            for class in 0..width {
+                let target = self.transitions[state * width + class];
+                inverse[target * width + class].push(state);
+            }
+        }
+
+        let mut blocks: Vec<Vec<DfaStateId>> = Vec::new();
+        let mut initial = HashMap::new();
+        let mut block_of = self
+            .terminals
+            .iter()
+            .zip(&self.trailing)
+            .zip(&self.at_end)
+            .enumerate()
+            .map(|(state, key)| {
+                let block = *initial.entry(key).or_insert_with(|| {
+                    blocks.push(Vec::new());
+                    blocks.len() - 1
+                });
+                blocks[block].push(state);
+                block
+            })
+            .collect::<Vec<_>>();
+
+        let mut worklist = (0..blocks.len()).collect::<Vec<_>>();
+        let mut in_worklist = vec![true; blocks.len()];
+        let mut marked = vec![false; len];
+        let mut counts = vec![0; len];
+        while let Some(splitter) = worklist.pop() {
+            in_worklist[splitter] = false;
+            let splitter = blocks[splitter].clone();
+            for class in 0..width {
+                // splitterへclassで遷移する状態に印を付け、触れたブロックを集める
+                let mut sources = Vec::new();
+                let mut touched = Vec::new();
+                for &target in &splitter {
+                    for &source in &inverse[target * width + class] {
+                        if marked[source] {
+                            continue;
+                        }
+                        marked[source] = true;
+                        sources.push(source);
+                        let block = block_of[source];
+                        if counts[block] == 0 {
+                            touched.push(block);
+                        }
+                        counts[block] += 1;
+                    }
+                }
+
+                for block in touched {
+                    if counts[block] < blocks[block].len() {
+                        let (inside, outside): (Vec<_>, Vec<_>) =
+                            blocks[block].iter().partition(|&&state| marked[state]);
+                        let new_block = blocks.len();
+                        outside
+                            .iter()
+                            .for_each(|&state| block_of[state] = new_block);
+                        let smaller = if inside.len() <= outside.len() {
+                            block
+                        } else {
+                            new_block
+                        };
+                        blocks[block] = inside;
+                        blocks.push(outside);
+                        in_worklist.push(false);
+                        // 分割前のブロックが未処理なら両方、そうでなければ小さい方だけ処理する
+                        let pushed = if in_worklist[block] {
+                            new_block
+                        } else {
+                            smaller
+                        };
+                        worklist.push(pushed);
+                        in_worklist[pushed] = true;
+                    }
+                    counts[block] = 0;
+                }
+                sources.into_iter().for_each(|state| marked[state] = false);
+            }
+        }
+
+        // 元の状態の番号が小さい順に番号を振り直す 死状態は0のまま
+        let mut renumber = vec![None; blocks.len()];
+        let mut representatives = Vec::new();
+        for (state, &block) in block_of.iter().enumerate() {
+            if renumber[block].is_none() {
+                renumber[block] = Some(representatives.len());
+                representatives.push(state);
+            }
+        }
+        let new_id = |state: DfaStateId| renumber[block_of[state]].unwrap();
+        let transitions = representatives
+            .iter()
+            .flat_map(|&state| {
+                self.transitions[state * width..(state + 1) * width]
+                    .iter()
+                    .map(|&target| new_id(target))
+            })
+            .collect::<Vec<_>>();
+        let terminals = representatives
+            .iter()
+            .map(|&state| self.terminals[state].clone())
+            .collect();
+        let trailing = representatives
+            .iter()
+            .map(|&state| self.trailing[state])
+            .collect();
+        let at_end = representatives
+            .iter()
+            .map(|&state| self.at_end[state].clone())
+            .collect();
+
+        let mut dfa = Dfa {
+            boundaries: self.boundaries.clone(),
+            transitions,
+            terminals,
+            trailing,
+            at_end,
+            starts: self.starts.iter().map(|&start| new_id(start)).collect(),
+            bol_starts: self.bol_starts.iter().map(|&start| new_id(start)).collect(),
+            eofs: self.eofs.clone(),
+        };
+        dfa.merge_classes();
+        dfa
+    }
+}
+
+impl<T> Dfa<T> {
+    /// 全ての状態で遷移先が同じ、隣り合う区間を一つにまとめる
+    fn merge_classes(&mut self) {
+        let width = self.boundaries.len();
+        let column = |class: usize| {
+            (0..self.len())
+                .map(|state| self.transitions[state * width + class])
+                .collect::<Vec<_>>()
+        };
+        let mut kept = vec![0];
+        for class in 1..width {
+            if column(class) != column(*kept.last().unwrap()) {
+                kept.push(class);
+            }
+        }
+        if kept.len() == width {
+            return;
+        }
+        self.transitions = (0..self.len())
+            .flat_map(|state| kept.iter().map(move |&class| (state, class)))
+            .map(|(state, class)| self.transitions[state * width + class])
+            .collect();
+        self.boundaries = kept.iter().map(|&class| self.boundaries[class]).collect();
+    }
+}
+
+/// ε閉包をソートして、状態集合の鍵として使える形にする
+fn closure<T, I: IntoIterator<Item = StateId>>(nfa: &Nfa<T>, states: I) -> Vec<StateId> {
+    let mut set = nfa.epsilon_closure(states);
+    set.sort_unstable();
+    set
+}
+
+#[cfg(test)]
+mod dfa_test {
+    use super::*;
+    use crate::nfa::NfaEdge;
+
+    #[test]
+    fn dead_state_loops() {
+        let mut nfa = Nfa::new();
+        let tail = nfa.add_terminal_node("a");
+        nfa.add_child(nfa.start(), NfaEdge::new_char('a'), tail);
+        let dfa = Dfa::from_nfa(&nfa);
+        assert_eq!(dfa.len(), 3);
+        assert!(dfa.terminal(Dfa::<&str>::DEAD).is_none());
+        assert_eq!(dfa.next(dfa.start(), 'b'), Dfa::<&str>::DEAD);
+        assert_eq!(dfa.next(Dfa::<&str>::DEAD, 'a'), Dfa::<&str>::DEAD);
+        let accepted = dfa.next(dfa.start(), 'a');
+        assert_eq!(dfa.terminal(accepted), Some(&"a"));
+        assert_eq!(dfa.next(accepted, 'a'), Dfa::<&str>::DEAD);
+    }
+
+    #[test]
+    fn boundaries_split_ranges() {
+        let mut nfa = Nfa::new();
+        let tail = nfa.add_terminal_node(());
+        nfa.add_child(nfa.start(), NfaEdge::new_char('b'), tail);
+        nfa.add_child(nfa.start(), NfaEdge::new_char('d'), tail);
+        let dfa = Dfa::from_nfa(&nfa);
+        assert_eq!(dfa.boundaries(), &['\0', 'b', 'c', 'd', 'e']);
+        assert_eq!(dfa.class_of('a'), 0);
+        assert_eq!(dfa.class_of('b'), 1);
+        assert_eq!(dfa.class_of('z'), 4);
+        assert_eq!(dfa.class_of(char::MAX), 4);
+        assert_eq!(dfa.transitions().len(), dfa.len() * 5);
+    }
+
+    #[test]
+    fn priority_wins() {
+        let mut nfa = Nfa::new();
+        let first = nfa.add_node();
+        let second = nfa.add_node();
+        nfa.add_child(nfa.start(), NfaEdge::new_char('a'), second);
+        nfa.add_child(nfa.start(), NfaEdge::new_char('a'), first);
+        nfa.set_terminal(first, "first");
+        nfa.set_terminal(second, "second");
+        let dfa = Dfa::from_nfa(&nfa);
+        assert_eq!(dfa.collect_terminal(&['a'], 0), vec![("first", 1)]);
+    }
+
+    #[test]
+    fn minimize_merges_equivalent() {
+        // start -a-> x -c-> end, start -b-> y -c-> end
+        let mut nfa = Nfa::new();
+        let x = nfa.add_node();
+        let y = nfa.add_node();
+        let end = nfa.add_terminal_node(());
+        nfa.add_child(nfa.start(), NfaEdge::new_char('a'), x);
+        nfa.add_child(nfa.start(), NfaEdge::new_char('b'), y);
+        nfa.add_child(x, NfaEdge::new_char('c'), end);
+        nfa.add_child(y, NfaEdge::new_char('c'), end);
+        let dfa = Dfa::from_nfa(&nfa);
+        assert_eq!(dfa.len(), 5);
+        let minimized = dfa.minimize();
+        assert_eq!(minimized.len(), 4);
+        assert_eq!(
+            minimized.next(minimized.start(), 'a'),
+            minimized.next(minimized.start(), 'b')
+        );
+        // 'a'と'b'は同じ区間にまとまる
+        assert_eq!(minimized.boundaries(), &['\0', 'a', 'c', 'd']);
+    }
+
+    #[test]
+    fn minimize_keeps_trailing_apart() {
+        // start -a-> x (Tail(1)), start -b-> y (区切り無し) は同じterminalでもまとめない
+        let mut nfa = Nfa::new();
+        let x = nfa.add_node();
+        let y = nfa.add_node();
+        nfa.add_child(nfa.start(), NfaEdge::new_char('a'), x);
+        nfa.add_child(nfa.start(), NfaEdge::new_char('b'), y);
+        nfa.set_terminal(x, ());
+        nfa.set_trailing(x, TrailingContext::Tail(1));
+        nfa.set_terminal(y, ());
+        let minimized = Dfa::from_nfa(&nfa).minimize();
+        assert_eq!(minimized.len(), 4);
+        let x = minimized.next(minimized.start(), 'a');
+        let y = minimized.next(minimized.start(), 'b');
+        assert_eq!(minimized.trailing(x), Some(TrailingContext::Tail(1)));
+        assert_eq!(minimized.trailing(y), None);
+    }
+
+    #[test]
+    fn minimize_keeps_dead_first() {
+        let mut nfa = Nfa::new();
+        let tail = nfa.add_terminal_node(());
+        nfa.add_child(nfa.start(), NfaEdge::new_char('a'), tail);
+        nfa.add_child(tail, NfaEdge::new_char('a'), tail);
+        let minimized = Dfa::from_nfa(&nfa).minimize();
+        assert!(minimized.terminal(Dfa::<()>::DEAD).is_none());
+        assert_eq!(minimized.next(Dfa::<()>::DEAD, 'a'), Dfa::<()>::DEAD);
+        assert_eq!(minimized.start(), 1);
+    }
+}
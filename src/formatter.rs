@@ -0,0 +1,256 @@
+//! `flex fmt`の実装
+//! 仕様のTOMLの`regex = "..."`を、構文木から書き戻した正規の形にそろえる
+//! TOMLは行ごとに書き換えるので、コメントや空行、テーブルの順番はそのまま残る
+//!
+//! 構文木には`{NAME}`と`(?i)`が残らないので、それらを使う正規表現は書き換えない
+//! 書き換えるのは、ルールのテーブルに一行で書いた`regex = ...`だけ
+use std::collections::HashMap;
+use std::ops::Range;
+
+use toml::value::Value;
+
+use crate::config::{ConfigError, Configs, Rule};
+use crate::regex_ast::RegexAst;
+use crate::regex_error::RegexError;
+use crate::regex_parser::parse_ast;
+use crate::regex_simplifier::simplify;
+use crate::regex_tokenizer::{Item, Regex};
+
+/// `format_spec`の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formatted {
+    /// 書き換えたTOML
+    pub source: String,
+    /// `regex`を書き換えたルールの名前
+    pub changed: Vec<String>,
+    /// (ルール, 先に書いたルール) の組
+    /// 簡約した構文木もスタート状態も同じなので、後のルールはトークンにならない
+    pub duplicates: Vec<(String, String)>,
+}
+
+/// 正規表現を正規の形に書き直す
+/// 簡約した構文木から書き戻すので、要らない括弧や重複した選択肢は消える
+/// `{NAME}`か`(?i)`を使っていれば`None`を返す
+pub fn canonical_regex(pattern: &str) -> Result<Option<String>, RegexError> {
+    let regex = Regex::new(pattern.to_string())?;
+    let items = regex.tokens_iter().collect::<Vec<_>>();
+    let flag = items
+        .windows(2)
+        .any(|pair| pair == [Item::BracketL, Item::ZeroOrOne]);
+    if flag || items.iter().any(|item| matches!(item, Item::Reference(_))) {
+        return Ok(None);
+    }
+    Ok(Some(simplify(parse_ast(&regex)?).to_string()))
+}
+
+/// `pattern`をTOMLの文字列にする
+/// `literal`なら、書ける限り`'...'`のリテラル文字列にする
+fn toml_string(pattern: &str, literal: bool) -> String {
+    if literal && !pattern.contains('\'') {
+        format!("'{}'", pattern)
+    } else {
+        let escaped = pattern.replace('\\', r"\\").replace('"', "\\\"");
+        format!("\"{}\"", escaped)
+    }
+}
+
+/// `line`が`regex = <文字列>`なら、文字列を書いた範囲 (バイト単位) と値を返す
+/// 後ろにコメントがあってもよい
+fn regex_value(line: &str) -> Option<(Range<usize>, String)> {
+    let (key, value) = line.split_once('=')?;
+    if key.trim() != "regex" {
+        return None;
+    }
+    let start = line.len() - value.trim_start().len();
+    // 残りが空白かコメントになる、一番短い文字列を探す
+    // `'''..'''`の先頭の`''`のように、途中で切れた所は残りで弾く
+    (start + 1..=line.len())
+        .filter(|&end| line.is_char_boundary(end))
+        .find_map(|end| {
+            let rest = line[end..].trim_start();
+            if !rest.is_empty() && !rest.starts_with('#') {
+                return None;
+            }
+            match format!("v = {}", &line[start..end])
+                .parse::<Value>()
+                .ok()?
+                .get("v")?
+            {
+                Value::String(value) => Some((start..end, value.clone())),
+                _ => None,
+            }
+        })
+}
+
+/// `[Name]`の行ならテーブル名を返す
+fn table_name(line: &str) -> Option<&str> {
+    let (name, _) = line.trim_start().strip_prefix('[')?.split_once(']')?;
+    Some(name.trim().trim_matches('"'))
+}
+
+/// 簡約した構文木とスタート状態が、先に書いたルールと同じルール
+fn duplicates(configs: &Configs) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut seen: Vec<(&Rule, RegexAst)> = Vec::new();
+    let mut duplicates = Vec::new();
+    for rule in configs.rules().iter().filter(|rule| !rule.is_eof()) {
+        let ast = simplify(configs.ast(rule)?);
+        let first = seen
+            .iter()
+            .find(|(first, first_ast)| *first_ast == ast && first.states() == rule.states());
+        match first {
+            Some((first, _)) => {
+                duplicates.push((rule.name().to_string(), first.name().to_string()))
+            }
+            None => seen.push((rule, ast)),
+        }
+    }
+    Ok(duplicates)
+}
+
+/// 仕様のTOMLの`regex`を正規の形に書き換える
+pub fn format_spec(source: &str) -> Result<Formatted, ConfigError> {
+    let configs = Configs::from_toml(source)?;
+    let mut canonical = HashMap::new();
+    for rule in configs.rules().iter().filter(|rule| !rule.is_eof()) {
+        let to_error = |error| ConfigError::Regex(rule.name().to_string(), error);
+        if let Some(pattern) = canonical_regex(rule.regex()).map_err(to_error)? {
+            canonical.insert(rule.name(), pattern);
+        }
+    }
+
+    let mut formatted = String::with_capacity(source.len());
+    let mut changed = Vec::new();
+    let mut table = None;
+    for line in source.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\n', '\r']);
+        if let Some(name) = table_name(body) {
+            table = Some(name);
+        } else if let Some((range, value)) = regex_value(body) {
+            let pattern = table.and_then(|name| Some((name, canonical.get(name)?)));
+            if let Some((name, pattern)) = pattern.filter(|(_, pattern)| **pattern != value) {
+                let literal = body[range.clone()].starts_with('\'');
+                formatted.push_str(&line[..range.start]);
+                formatted.push_str(&toml_string(pattern, literal));
+                formatted.push_str(&line[range.end..]);
+                changed.push(name.to_string());
+                continue;
+            }
+        }
+        formatted.push_str(line);
+    }
+
+    Ok(Formatted {
+        source: formatted,
+        changed,
+        duplicates: duplicates(&configs)?,
+    })
+}
+
+#[cfg(test)]
+mod formatter_test {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        let canonical = |pattern| canonical_regex(pattern).unwrap();
+        assert_eq!(canonical("[0-9]{1,}"), Some(r"\d+".to_string()));
+        assert_eq!(canonical(r#""a.b""#), Some(r"a\.b".to_string()));
+        assert_eq!(canonical("{DIGIT}+"), None);
+        assert_eq!(canonical("(?i)if"), None);
+        assert!(canonical_regex("a(").is_err());
+    }
+
+    #[test]
+    fn regex_line() {
+        assert_eq!(
+            regex_value(r#"regex = "a#b" # c"#),
+            Some((8..13, "a#b".to_string()))
+        );
+        assert_eq!(regex_value("regex='a'"), Some((6..9, "a".to_string())));
+        assert_eq!(
+            regex_value("regex = '''a'''"),
+            Some((8..15, "a".to_string()))
+        );
+        assert_eq!(regex_value("states = [\"A\"]"), None);
+        assert_eq!(regex_value("regex = true"), None);
+    }
+
+    #[test]
+    fn rewrite() {
+        let source = "# keywords\n\
+                      [If]\n\
+                      regex = \"i[f]\" # if\n\
+                      \n\
+                      [definitions]\n\
+                      DIGIT = '[0-9]'\n\
+                      \n\
+                      [Number]\n\
+                      regex = '{DIGIT}{1,}'\n\
+                      \n\
+                      [Float]\n\
+                      regex = '[0-9]+\\.[0-9]*'\n\
+                      \n\
+                      [Quote]\n\
+                      regex = \"[']\"\n\
+                      \n\
+                      [Eof]\n\
+                      regex = \"<<EOF>>\"\n";
+        let formatted = format_spec(source).unwrap();
+        let ans = "# keywords\n\
+                   [If]\n\
+                   regex = \"if\" # if\n\
+                   \n\
+                   [definitions]\n\
+                   DIGIT = '[0-9]'\n\
+                   \n\
+                   [Number]\n\
+                   regex = '{DIGIT}{1,}'\n\
+                   \n\
+                   [Float]\n\
+                   regex = '\\d+\\.\\d*'\n\
+                   \n\
+                   [Quote]\n\
+                   regex = \"'\"\n\
+                   \n\
+                   [Eof]\n\
+                   regex = \"<<EOF>>\"\n";
+        assert_eq!(formatted.source, ans);
+        assert_eq!(formatted.changed, vec!["If", "Float", "Quote"]);
+        assert!(formatted.duplicates.is_empty());
+        assert_eq!(
+            format_spec(&formatted.source).unwrap().changed,
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn literal_with_quote() {
+        let formatted = format_spec("[Quote]\nregex = '''[']{1,}'''\n").unwrap();
+        assert_eq!(formatted.source, "[Quote]\nregex = \"'+\"\n");
+    }
+
+    #[test]
+    fn cosmetic_duplicates() {
+        let source = "[definitions]\n\
+                      DIGIT = \"[0-9]\"\n\
+                      [A]\n\
+                      regex = \"{DIGIT}+\"\n\
+                      [B]\n\
+                      regex = \"[0-9]{1,}\"\n\
+                      [C]\n\
+                      regex = \"(?i)x\"\n\
+                      [D]\n\
+                      regex = \"[xX]\"\n\
+                      [E]\n\
+                      regex = \"[xX]\"\n\
+                      states = [\"S\"]\n\
+                      [states]\n\
+                      S = \"inclusive\"\n";
+        let formatted = format_spec(source).unwrap();
+        let duplicates = vec![
+            ("B".to_string(), "A".to_string()),
+            ("D".to_string(), "C".to_string()),
+        ];
+        assert_eq!(formatted.duplicates, duplicates);
+    }
+}
@@ -0,0 +1,122 @@
+//! DFAを使った字句解析器
+//! 現在の位置から最長一致するトークンを切り出すことを繰り返す
+//! 同じ長さで一致するルールが複数あれば、優先度の高いルールを選ぶ
+//! どのルールにも一致しなければ、一文字を`LexToken::Error`として返して先に進む
+//!
+//! スタート状態 (モード) は`Dfa::starts`の添字で表し、最初は0
+//! `begin`で切り替え、`push_state`と`pop_state`で入れ子にできる
+//! 入力の先頭と改行の直後では、`^`のルールも使える`Dfa::bol_starts`から読む
+//! 入力を読み終えたら、今のスタート状態に`<<EOF>>`のルールがあればそのトークンを空の範囲で一度返す
+use crate::dfa::Dfa;
+
+/// 入力の中の範囲 (バイト単位)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// 字句解析器が返すトークン
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LexToken<T> {
+    Token(T),
+    /// どのルールにも一致しなかった
+    Error,
+}
+
+pub struct Lexer<'a, T> {
+    dfa: &'a Dfa<T>,
+    input: &'a str,
+    position: usize,
+    state: usize,
+    stack: Vec<usize>,
+    /// 入力の終わりのトークンを返したか
+    finished: bool,
+}
+
+impl<'a, T> Lexer<'a, T> {
+    pub fn new(dfa: &'a Dfa<T>, input: &'a str) -> Self {
+        Self {
+            dfa,
+            input,
+            position: 0,
+            state: 0,
+            stack: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// 次に読む位置 (バイト単位)
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 今のスタート状態
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    /// スタート状態を`state`に切り替え、切り替えたら`true`を返す
+    /// `state`が`Dfa::starts`の範囲外なら何もせず`false`を返す
+    pub fn begin(&mut self, state: usize) -> bool {
+        if state >= self.dfa.starts().len() {
+            return false;
+        }
+        self.state = state;
+        true
+    }
+
+    /// 今のスタート状態を積んでから`state`に切り替え、切り替えたら`true`を返す
+    /// `state`が範囲外なら、積まずに`false`を返す
+    pub fn push_state(&mut self, state: usize) -> bool {
+        let current = self.state;
+        if !self.begin(state) {
+            return false;
+        }
+        self.stack.push(current);
+        true
+    }
+
+    /// `push_state`の前のスタート状態に戻し、戻った状態を返す
+    /// 積んだ状態が無ければ何もせず`None`を返す
+    pub fn pop_state(&mut self) -> Option<usize> {
+        self.state = self.stack.pop()?;
+        Some(self.state)
+    }
+}
+
+impl<T: Clone> Iterator for Lexer<'_, T> {
+    type Item = (LexToken<T>, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.position..];
+        let start = self.position;
+        let Some(c) = rest.chars().next() else {
+            if self.finished {
+                return None;
+            }
+            self.finished = true;
+            let terminal = self.dfa.eof(self.state)?.clone();
+            return Some((LexToken::Token(terminal), Span::new(start, start)));
+        };
+        // 空文字列への一致では先に進めないので、一致しなかったものとして扱う
+        let at_bol = start == 0 || self.input[..start].ends_with('\n');
+        let start_state = if at_bol {
+            self.dfa.bol_starts()[self.state]
+        } else {
+            self.dfa.starts()[self.state]
+        };
+        let (token, len) = match self.dfa.longest_match_from(start_state, rest) {
+            Some((terminal, len)) if len > 0 => (LexToken::Token(terminal.clone()), len),
+            _ => (LexToken::Error, c.len_utf8()),
+        };
+        self.position += len;
+        Some((token, Span::new(start, self.position)))
+    }
+}
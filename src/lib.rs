@@ -1,5 +1,15 @@
-// pub mod automaton;
+pub mod char_class;
+pub mod codegen;
+pub mod config;
 pub mod dfa;
+pub mod formatter;
+pub mod lexer;
 pub mod nfa;
+pub mod regex_ast;
+pub mod regex_compiler;
+pub mod regex_error;
 pub mod regex_parser;
+pub mod regex_simplifier;
 pub mod regex_tokenizer;
+pub mod unicode;
+mod unicode_tables;
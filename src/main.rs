@@ -1,105 +1,104 @@
+use std::fs::{read_to_string, write};
+use std::path::PathBuf;
+use std::process::exit;
+
+use clap::{Parser, Subcommand};
+
+use flex::codegen::generate;
+use flex::config::{ConfigError, Configs};
+use flex::formatter::format_spec;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about=None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// path to the config toml file.
+    #[clap(short, long, value_name = "INPUT_TOML", required = true)]
+    input: Option<PathBuf>,
+
+    /// output path
+    #[clap(short, long, value_name = "OUTPUT_RS", required = true)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// rewrite every `regex` in the config toml files in canonical form.
+    Fmt {
+        /// only report the rules to rewrite, and fail if there are any.
+        #[clap(long)]
+        check: bool,
+
+        /// paths to the config toml files.
+        #[clap(value_name = "INPUT_TOML", required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+/// 仕様から字句解析器のコードを生成して書き込む
+fn codegen(input: &PathBuf, output: &PathBuf) {
+    let code = match Configs::from_path(input).and_then(|configs| generate(&configs)) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{}", error);
+            exit(1);
+        }
+    };
+    if let Err(error) = write(output, code) {
+        eprintln!("cannot write to {:?}: {}", output, error);
+        exit(1);
+    }
+}
+
+/// `files`の`regex`を正規の形に書き換える
+/// `check`なら書き換えずに報告だけして、書き換えるルールか重複したルールがあれば失敗する
+fn fmt(files: &[PathBuf], check: bool) {
+    let mut failed = false;
+    for path in files {
+        let formatted = read_to_string(path)
+            .map_err(|error| ConfigError::Io(path.clone(), error))
+            .and_then(|source| format_spec(&source));
+        let formatted = match formatted {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{:?}: {}", path, error);
+                exit(1);
+            }
+        };
+        for (rule, first) in &formatted.duplicates {
+            eprintln!(
+                "{:?}: `{}` matches the same input as `{}`",
+                path, rule, first
+            );
+            failed |= check;
+        }
+        if formatted.changed.is_empty() {
+            continue;
+        }
+        if check {
+            for rule in &formatted.changed {
+                println!("{:?}: `{}.regex` is not in canonical form", path, rule);
+            }
+            failed = true;
+        } else if let Err(error) = write(path, formatted.source) {
+            eprintln!("cannot write to {:?}: {}", path, error);
+            exit(1);
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
 fn main() {
-    println!("Oppai");
+    let arg = Args::parse();
+    match (arg.command, arg.input, arg.output) {
+        (Some(Command::Fmt { check, files }), _, _) => fmt(&files, check),
+        (None, Some(input), Some(output)) => codegen(&input, &output),
+        // subcommandが無ければ`input`と`output`は必須
+        (None, _, _) => unreachable!(),
+    }
 }
-// use std::fs::read_to_string;
-// use std::iter::Iterator;
-// use std::path::{Path, PathBuf};
-// use std::str::FromStr;
-// use std::ops::{Index, IndexMut};
-//
-// use toml::value::Value;
-//
-// use clap::Parser;
-//
-// struct Item {
-//     name: String,
-//     regex: String,
-// }
-//
-// impl Item {
-//     fn new(name: String, regex: String) -> Self {
-//         Self { name, regex }
-//     }
-//
-//     fn name(&self) -> String {
-//         self.name.clone()
-//     }
-//
-//     fn regex(&self) -> String {
-//         self.regex.clone()
-//     }
-// }
-//
-// struct Configs {
-//     inner: Vec<Item>,
-// }
-//
-// impl Configs {
-//     fn new<P: AsRef<Path> + Clone>(path: P) -> Self {
-//         let ref config_string = read_to_string(path.clone())
-//             .expect(&format!("filename {:?} is not exists", path.as_ref()));
-//         let toml = Value::from_str(config_string)
-//             .expect(&format!("filename {:?} is not toml file", path.as_ref()));
-//
-//         let mut inner = Vec::new();
-//
-//         match toml {
-//             Value::Table(map) => {
-//                 map.into_iter().fold(&mut inner, |prev, x| {
-//                     let (ref name, ref value) = x;
-//                     let value = value
-//                         .as_table()
-//                         .expect("this is not what I expect toml format");
-//                     let regex = value.get("regex").expect("regex is must.");
-//                     let item = Item::new(name.clone(), regex.to_string());
-//                     prev.push(item);
-//                     prev
-//                 });
-//             }
-//             _ => {
-//                 unreachable!()
-//             }
-//         }
-//
-//         Configs { inner }
-//     }
-//
-//     fn to_enum_code(&self) -> String {
-//         let mut code = "pub enum Token { ".to_string();
-//
-//         self.inner.iter().fold(&mut code, |prev, x| {
-//             let add = format!("{}, ", x.name());
-//             prev.push_str(&add);
-//             prev
-//         });
-//         code.push_str("}");
-//         code
-//     }
-// }
-//
-// #[test]
-// fn test_parse_toml() {
-//     let code = Configs::new("./test/test_toml_parse.toml").to_enum_code();
-//     let ans = "pub enum Token { Manko, Tinko, }".to_string();
-//     assert_eq!(ans, code);
-// }
-//
-// #[derive(Parser, Debug)]
-// #[clap(author, version, about, long_about=None)]
-// struct Args {
-//     /// path to the config toml file.
-//     #[clap(short, long, value_name="INPUT_TOML")]
-//     input: PathBuf,
-//
-//     /// output path
-//     #[clap(short, long, value_name="OUTPUT_RS")]
-//     output: PathBuf
-// }
-//
-// fn main() {
-//     let arg = Args::parse();
-//     let toml_path = arg.input;
-//     let output_path = arg.output;
-//     let enum_code = Configs::new(toml_path).to_enum_code();
-//     std::fs::write(output_path, enum_code).expect("cann't write to output");
-// }
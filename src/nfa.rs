@@ -1,17 +1,21 @@
 //! NFAに関する実装
 //! このファイルでは、トークナイズ以外のNFAに関する実装を行う
-use std::cell::RefCell;
+//!
+//! NFAの状態は`Nfa`が`Vec`でまとめて持ち、`StateId`で指す
+//! `Rc<RefCell<_>>`で繋ぐと`*`の作る循環でメモリがリークするため、
+//! 状態同士はインデックスだけで繋ぐ
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::hash::Hash;
-use std::iter::Map;
-use std::rc::Rc;
+use std::ops::{Index, IndexMut};
 
+use crate::char_class::CharClass;
 use crate::regex_tokenizer::Item;
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub enum NfaEdge {
     Alphabet(Item),
+    /// 文字クラス 一回の遷移で範囲内のどれか一文字を受理する
+    Class(CharClass),
     Epsilon,
 }
 
@@ -20,6 +24,7 @@ impl PartialEq<char> for NfaEdge {
         match self {
             NfaEdge::Epsilon => false,
             NfaEdge::Alphabet(c) => c == other,
+            NfaEdge::Class(class) => class.contains(*other),
         }
     }
 }
@@ -30,125 +35,402 @@ impl PartialEq<NfaEdge> for char {
 }
 
 impl NfaEdge {
-    pub(crate) fn new_alphabet(c: Item) -> Self {
-        NfaEdge::Alphabet(c)
+    pub(crate) fn new_class(class: CharClass) -> Self {
+        NfaEdge::Class(class)
     }
 
-    fn new_char(c: char) -> Self {
+    pub fn new_char(c: char) -> Self {
         NfaEdge::Alphabet(c.into())
     }
 
-    pub(crate) fn new_epsilon() -> Self {
+    pub fn new_epsilon() -> Self {
         NfaEdge::Epsilon
     }
+
+    pub fn is_epsilon(&self) -> bool {
+        matches!(self, NfaEdge::Epsilon)
+    }
+
+    /// この遷移で読める文字の集合 (ε遷移は`None`)
+    pub fn char_class(&self) -> Option<CharClass> {
+        match self {
+            NfaEdge::Epsilon => None,
+            NfaEdge::Class(class) => Some(class.clone()),
+            NfaEdge::Alphabet(Item::Any) => Some(CharClass::any()),
+            NfaEdge::Alphabet(item) => item
+                .shorthand_class()
+                .or_else(|| item.to_char().map(CharClass::from_char)),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct NfaNode<T>
-where
-    T: Clone + Debug,
-{
-    terminal: Option<T>,
-    child: HashMap<NfaEdge, Vec<Rc<RefCell<NfaNode<T>>>>>,
+/// `Nfa`の中の状態を指すインデックス
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct StateId(usize);
+
+impl StateId {
+    pub fn index(self) -> usize {
+        self.0
+    }
 }
 
-impl<T> Default for NfaNode<T> 
-where
-    T: Clone + Debug
-{
-    fn default() -> Self {
-        Self { terminal: None, child: HashMap::new() }
+/// `r/s`のルールで、一致した文字列のうちどこまでをトークンにするか
+/// `r`か`s`のどちらかの長さ (文字数) が決まっていれば区切れる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrailingContext {
+    /// `r`の長さが決まっている
+    Head(usize),
+    /// `s`の長さが決まっている
+    Tail(usize),
+}
+
+impl TrailingContext {
+    /// `r/s`に一致した`matched`のうち、`r`の部分のバイト数
+    pub fn token_len(self, matched: &str) -> usize {
+        match self {
+            Self::Head(len) => matched
+                .char_indices()
+                .nth(len)
+                .map_or(matched.len(), |(offset, _)| offset),
+            Self::Tail(0) => matched.len(),
+            Self::Tail(len) => matched
+                .char_indices()
+                .nth_back(len - 1)
+                .map_or(0, |(offset, _)| offset),
+        }
     }
 }
 
-impl<T> NfaNode<T>
-where
-    T: Clone + Debug,
-{
-    fn new_terminal(t: T) -> Self {
+/// NFAの状態一つ分
+/// 遷移先は`StateId`で持つ
+/// `priority`は受理状態のルールの優先度で、小さいほど優先される
+/// `trailing`は`r/s`のルールの受理状態だけが持つ
+/// `eof_only`の受理状態は、入力を最後まで読んだ時だけ受理する (`r$`の`r`の終わり)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NfaNode<T> {
+    terminal: Option<T>,
+    priority: usize,
+    trailing: Option<TrailingContext>,
+    eof_only: bool,
+    child: Vec<(NfaEdge, StateId)>,
+}
+
+impl<T> Default for NfaNode<T> {
+    fn default() -> Self {
         Self {
-            terminal: Some(t),
-            child: HashMap::new(),
+            terminal: None,
+            priority: 0,
+            trailing: None,
+            eof_only: false,
+            child: Vec::new(),
         }
     }
+}
 
-    pub(crate) fn new_non_terminal() -> Self {
+impl<T> NfaNode<T> {
+    pub fn new_terminal(t: T) -> Self {
         Self {
-            terminal: None,
-            child: HashMap::new(),
+            terminal: Some(t),
+            ..Self::default()
         }
     }
 
-    pub(crate) fn add_child(&mut self, edge: NfaEdge, child: Rc<RefCell<Self>>) {
-        self.child.entry(edge).or_default().push(child);
+    pub fn new_non_terminal() -> Self {
+        Self::default()
     }
 
-    pub(crate) fn add_edge_nul_target_node(&mut self, edge: NfaEdge) {
-        self.child.entry(edge).or_default();
+    pub fn add_child(&mut self, edge: NfaEdge, child: StateId) {
+        self.child.push((edge, child));
     }
 
     pub fn set_terminal(&mut self, terminal: T) {
         self.terminal = Some(terminal);
     }
 
-    fn is_terminal(&self) -> bool {
+    pub fn terminal(&self) -> Option<&T> {
+        self.terminal.as_ref()
+    }
+
+    pub fn is_terminal(&self) -> bool {
         self.terminal.is_some()
     }
 
-    fn _extract_child(&self, edge: NfaEdge) -> Option<&Vec<Rc<RefCell<Self>>>> {
-        self.child.get(&edge)
+    /// 受理状態ならルールの優先度を返す
+    pub fn priority(&self) -> Option<usize> {
+        self.terminal.as_ref().map(|_| self.priority)
     }
 
-    fn _extract_child_map<B, F>(
-        &'_ self,
-        edge: NfaEdge,
-        f: F,
-    ) -> Option<Map<std::slice::Iter<'_, Rc<RefCell<Self>>>, F>>
-    where
-        F: FnMut(&Rc<RefCell<Self>>) -> B,
-    {
-        self._extract_child(edge).map(|v| v.iter().map(f))
+    pub fn trailing(&self) -> Option<TrailingContext> {
+        self.trailing
     }
 
-    pub fn collect_terminal(&self, query: &Vec<char>, idx: usize) -> Vec<(T, usize)> {
-        let mut res = Vec::new();
+    pub fn eof_only(&self) -> bool {
+        self.eof_only
+    }
+
+    pub fn edges(&self) -> &[(NfaEdge, StateId)] {
+        &self.child
+    }
+}
+
+/// 全ての状態を持つNFA
+/// `Nfa::new`で作った時点で開始状態(`start`)が一つだけある
+/// スタート状態 (字句解析器のモード) ごとの開始状態は`add_start`で増やす
+/// `^`のルールは、行頭でだけ使う開始状態 (`bol_start`) から繋ぐ
+/// 開始状態ごとに、入力の終わりで返すterminal (`<<EOF>>`のルール) を一つ持てる
+/// 受理状態は設定した順に優先度が付き、先に設定したルールほど優先される
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nfa<T> {
+    nodes: Vec<NfaNode<T>>,
+    rules: usize,
+    starts: Vec<StateId>,
+    bol_starts: Vec<Option<StateId>>,
+    eofs: Vec<Option<T>>,
+}
 
-        if self.is_terminal() {
-            res.push((self.terminal.clone().unwrap(), idx));
+impl<T> Default for Nfa<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<StateId> for Nfa<T> {
+    type Output = NfaNode<T>;
+    fn index(&self, id: StateId) -> &Self::Output {
+        &self.nodes[id.0]
+    }
+}
+
+impl<T> IndexMut<StateId> for Nfa<T> {
+    fn index_mut(&mut self, id: StateId) -> &mut Self::Output {
+        &mut self.nodes[id.0]
+    }
+}
+
+impl<T> Nfa<T> {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![NfaNode::new_non_terminal()],
+            rules: 0,
+            starts: vec![StateId(0)],
+            bol_starts: vec![None],
+            eofs: vec![None],
         }
+    }
+
+    /// 開始状態
+    pub fn start(&self) -> StateId {
+        self.starts[0]
+    }
+
+    /// 全ての開始状態 先頭は`start`
+    pub fn starts(&self) -> &[StateId] {
+        &self.starts
+    }
 
-        let epsilons = self
-            ._extract_child_map(NfaEdge::Epsilon, |rc_refcell_node| {
-                let node_refcell = &**rc_refcell_node;
-                node_refcell.borrow().collect_terminal(query, idx)
-            })
-            .map(|v| v.flatten().collect::<Vec<_>>())
-            .unwrap_or_default();
-        res.extend(epsilons);
-
-        if idx == query.len() {
-            return res;
+    /// 開始状態を追加する
+    pub fn add_start(&mut self) -> StateId {
+        let id = self.add_node();
+        self.starts.push(id);
+        self.bol_starts.push(None);
+        self.eofs.push(None);
+        id
+    }
+
+    /// `idx`番目の開始状態で、入力の終わりに返すterminalを設定する
+    pub fn set_eof(&mut self, idx: usize, terminal: T) {
+        self.eofs[idx] = Some(terminal);
+    }
+
+    /// `starts`と同じ順の、入力の終わりに返すterminal
+    pub fn eofs(&self) -> &[Option<T>] {
+        &self.eofs
+    }
+
+    /// `idx`番目の開始状態の、行頭でだけ使う開始状態 無ければ作る
+    /// 行頭ではそこからεで`starts()[idx]`にも進む
+    pub fn bol_start(&mut self, idx: usize) -> StateId {
+        if let Some(id) = self.bol_starts[idx] {
+            return id;
         }
+        let id = self.add_node();
+        let start = self.starts[idx];
+        self.add_child(id, NfaEdge::new_epsilon(), start);
+        self.bol_starts[idx] = Some(id);
+        id
+    }
+
+    /// `starts`と同じ順の、行頭で使う開始状態
+    /// `^`のルールが無ければ`starts`と同じ状態になる
+    pub fn bol_starts(&self) -> Vec<StateId> {
+        self.bol_starts
+            .iter()
+            .zip(&self.starts)
+            .map(|(bol, start)| bol.unwrap_or(*start))
+            .collect()
+    }
 
-        let non_epsilons = self
-            ._extract_child_map(NfaEdge::new_char(query[idx]), |rc_refcell_node| {
-                let node_refcell = &**rc_refcell_node;
-                node_refcell.borrow().collect_terminal(query, idx + 1)
-            })
-            .map(|v| v.flatten().collect::<Vec<_>>())
-            .unwrap_or_default();
-        res.extend(non_epsilons);
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
 
-        res
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// 受理状態を追加した場合は、次の優先度を割り当てる
+    pub fn push(&mut self, mut node: NfaNode<T>) -> StateId {
+        if node.is_terminal() {
+            node.priority = self.next_priority();
+        }
+        self.nodes.push(node);
+        StateId(self.nodes.len() - 1)
+    }
+
+    fn next_priority(&mut self) -> usize {
+        self.rules += 1;
+        self.rules - 1
+    }
+
+    /// 非受理状態を追加する
+    pub fn add_node(&mut self) -> StateId {
+        self.push(NfaNode::new_non_terminal())
+    }
+
+    /// 受理状態を追加する
+    pub fn add_terminal_node(&mut self, terminal: T) -> StateId {
+        self.push(NfaNode::new_terminal(terminal))
+    }
+
+    /// `from`から`to`への遷移を追加する
+    pub fn add_child(&mut self, from: StateId, edge: NfaEdge, to: StateId) {
+        self[from].add_child(edge, to);
+    }
+
+    /// `id`を受理状態にし、次の優先度を割り当てる
+    pub fn set_terminal(&mut self, id: StateId, terminal: T) {
+        let priority = self.next_priority();
+        let node = &mut self[id];
+        node.set_terminal(terminal);
+        node.priority = priority;
+    }
+
+    /// `id`を`r/s`のルールの受理状態にする
+    pub fn set_trailing(&mut self, id: StateId, trailing: TrailingContext) {
+        self[id].trailing = Some(trailing);
+    }
+
+    /// `id`を入力の終わりでだけ受理する状態にする
+    pub fn set_eof_only(&mut self, id: StateId) {
+        self[id].eof_only = true;
+    }
+
+    pub fn states(&self) -> impl Iterator<Item = (StateId, &NfaNode<T>)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (StateId(idx), node))
+    }
+}
+
+impl<T: Clone> Nfa<T> {
+    /// `start`から辿れる部分グラフを複製し、複製後の (start, end) を返す
+    pub(crate) fn duplicate(&mut self, start: StateId, end: StateId) -> (StateId, StateId) {
+        // 辿れる状態に、複製先のStateIdを末尾から順に割り当てる
+        let mut map = HashMap::new();
+        let mut order = Vec::new();
+        let mut stack = vec![start];
+        while let Some(src) = stack.pop() {
+            if map.contains_key(&src) {
+                continue;
+            }
+            map.insert(src, StateId(self.nodes.len() + order.len()));
+            order.push(src);
+            stack.extend(self[src].child.iter().map(|(_, child)| *child));
+        }
+
+        for src in order {
+            let node = NfaNode {
+                terminal: self[src].terminal.clone(),
+                priority: self[src].priority,
+                trailing: self[src].trailing,
+                eof_only: self[src].eof_only,
+                child: self[src]
+                    .child
+                    .iter()
+                    .map(|(edge, child)| (edge.clone(), map[child]))
+                    .collect(),
+            };
+            self.nodes.push(node);
+        }
+        (map[&start], map[&end])
+    }
+
+    /// 開始状態から`query[idx..]`を読み、到達した受理状態の
+    /// (terminal, 読み終わった位置) を全て返す
+    /// 有効な状態の集合を一文字ずつ進めるので、計算量は O(文字数 * 状態数)
+    /// `idx`が行頭なら行頭の開始状態から読む
+    pub fn collect_terminal(&self, query: &[char], idx: usize) -> Vec<(T, usize)> {
+        let mut res = Vec::new();
+        let start = if idx == 0 || query[idx - 1] == '\n' {
+            self.bol_starts()[0]
+        } else {
+            self.start()
+        };
+        let mut current = self.epsilon_closure([start]);
+        let mut position = idx;
+        loop {
+            let terminals = current
+                .iter()
+                .filter(|id| position == query.len() || !self[**id].eof_only())
+                .filter_map(|id| self[*id].terminal())
+                .map(|terminal| (terminal.clone(), position));
+            res.extend(terminals);
+
+            if position == query.len() || current.is_empty() {
+                return res;
+            }
+            current = self.epsilon_closure(self.step(&current, query[position]));
+            position += 1;
+        }
     }
 }
 
-// pub struct Nfa<T>
-// where
-//     T: Debug + Clone
-// {
-//     start: Rc<NfaNode<T, C>>
-// }
+impl<T> Nfa<T> {
+    /// `states`からε遷移だけで辿れる状態を、深さ優先で見つけた順に返す
+    /// 訪問済みの状態は二度辿らないので、εの循環があっても止まる
+    pub fn epsilon_closure<I: IntoIterator<Item = StateId>>(&self, states: I) -> Vec<StateId> {
+        let mut visited = vec![false; self.len()];
+        let mut res = Vec::new();
+        let mut stack = states.into_iter().collect::<Vec<_>>();
+        stack.reverse();
+        while let Some(id) = stack.pop() {
+            if visited[id.0] {
+                continue;
+            }
+            visited[id.0] = true;
+            res.push(id);
+            let epsilons = self[id]
+                .edges()
+                .iter()
+                .rev()
+                .filter(|(edge, _)| edge.is_epsilon())
+                .map(|(_, child)| *child);
+            stack.extend(epsilons);
+        }
+        res
+    }
+
+    /// `states`のそれぞれから`c`で遷移できる先を返す (ε遷移は辿らない)
+    pub fn step(&self, states: &[StateId], c: char) -> Vec<StateId> {
+        states
+            .iter()
+            .flat_map(|id| self[*id].edges())
+            .filter(|(edge, _)| *edge == c)
+            .map(|(_, child)| *child)
+            .collect()
+    }
+}
 
 #[cfg(test)]
 mod collect_node_test {
@@ -156,849 +438,176 @@ mod collect_node_test {
     use super::*;
 
     macro_rules! collect_node_utils {
-        ($head:expr, $vec:expr, $ans:expr) => {
-            assert_eq!($head.collect_terminal(&$vec, 0), $ans);
+        ($nfa:expr, $vec:expr, $ans:expr) => {
+            assert_eq!($nfa.collect_terminal(&$vec, 0), $ans);
         };
     }
 
     #[test]
     fn two_char() {
-        let mut head = NfaNode::new_non_terminal();
-        let tail = NfaNode::new_terminal("Terminal".to_string());
-        head.add_child(NfaEdge::new_char('a'), Rc::new(RefCell::new(tail)));
-        collect_node_utils!(head, vec!['a'], vec![("Terminal".to_string(), 1)]);
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let tail = nfa.add_terminal_node("Terminal".to_string());
+        nfa.add_child(head, NfaEdge::new_char('a'), tail);
+        collect_node_utils!(nfa, ['a'], vec![("Terminal".to_string(), 1)]);
     }
 
     #[test]
     fn two_epsilon() {
-        let mut head = NfaNode::new_non_terminal();
-        let tail = NfaNode::new_terminal("Terminal".to_string());
-        head.add_child(NfaEdge::Epsilon, Rc::new(RefCell::new(tail)));
-        collect_node_utils!(head, vec!['a'], vec![("Terminal".to_string(), 0)]);
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let tail = nfa.add_terminal_node("Terminal".to_string());
+        nfa.add_child(head, NfaEdge::Epsilon, tail);
+        collect_node_utils!(nfa, ['a'], vec![("Terminal".to_string(), 0)]);
     }
 
     #[test]
     fn three_ep_sandwich() {
-        let mut head = NfaNode::new_non_terminal();
-        let second = Rc::new(RefCell::new(NfaNode::new_non_terminal()));
-        let third = Rc::new(RefCell::new(NfaNode::new_non_terminal()));
-        let tail = Rc::new(RefCell::new(NfaNode::new_terminal("Terminal".to_string())));
-        (*third).borrow_mut().add_child(NfaEdge::Epsilon, tail);
-        (*second)
-            .borrow_mut()
-            .add_child(NfaEdge::new_char('a'), third);
-        head.add_child(NfaEdge::Epsilon, second);
-        collect_node_utils!(head, vec!['a'], vec![("Terminal".to_string(), 1)]);
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let second = nfa.add_node();
+        let third = nfa.add_node();
+        let tail = nfa.add_terminal_node("Terminal".to_string());
+        nfa.add_child(third, NfaEdge::Epsilon, tail);
+        nfa.add_child(second, NfaEdge::new_char('a'), third);
+        nfa.add_child(head, NfaEdge::Epsilon, second);
+        collect_node_utils!(nfa, ['a'], vec![("Terminal".to_string(), 1)]);
     }
 
     #[test]
     fn skip_connections() {
-        let mut head = NfaNode::new_non_terminal();
-        let second = Rc::new(RefCell::new(NfaNode::new_non_terminal()));
-        let tail = Rc::new(RefCell::new(NfaNode::new_terminal("Terminal".to_string())));
-        (*second)
-            .borrow_mut()
-            .add_child(NfaEdge::Epsilon, Rc::clone(&tail));
-        head.add_child(NfaEdge::new_char('a'), second);
-        head.add_child(NfaEdge::Epsilon, tail);
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let second = nfa.add_node();
+        let tail = nfa.add_terminal_node("Terminal".to_string());
+        nfa.add_child(second, NfaEdge::Epsilon, tail);
+        nfa.add_child(head, NfaEdge::new_char('a'), second);
+        nfa.add_child(head, NfaEdge::Epsilon, tail);
         collect_node_utils!(
-            head,
-            vec!['a'],
+            nfa,
+            ['a'],
             vec![("Terminal".to_string(), 0), ("Terminal".to_string(), 1)]
         );
     }
 
     #[test]
     fn multi_terminal() {
-        let mut head = NfaNode::new_non_terminal();
-        let second1 = Rc::new(RefCell::new(NfaNode::new_non_terminal()));
-        let second2 = Rc::new(RefCell::new(NfaNode::new_non_terminal()));
-        let terminal1 = Rc::new(RefCell::new(NfaNode::new_terminal("Terminal1")));
-        let terminal2 = Rc::new(RefCell::new(NfaNode::new_terminal("Terminal2")));
-        head.add_child(NfaEdge::new_char('a'), Rc::clone(&second1));
-        head.add_child(NfaEdge::new_char('a'), Rc::clone(&second2));
-        (*second1)
-            .borrow_mut()
-            .add_child(NfaEdge::new_epsilon(), terminal1);
-        (*second2)
-            .borrow_mut()
-            .add_child(NfaEdge::new_epsilon(), terminal2);
-        collect_node_utils!(head, vec!['a'], vec![("Terminal1", 1), ("Terminal2", 1)]);
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let second1 = nfa.add_node();
+        let second2 = nfa.add_node();
+        let terminal1 = nfa.add_terminal_node("Terminal1");
+        let terminal2 = nfa.add_terminal_node("Terminal2");
+        nfa.add_child(head, NfaEdge::new_char('a'), second1);
+        nfa.add_child(head, NfaEdge::new_char('a'), second2);
+        nfa.add_child(second1, NfaEdge::new_epsilon(), terminal1);
+        nfa.add_child(second2, NfaEdge::new_epsilon(), terminal2);
+        collect_node_utils!(nfa, ['a'], vec![("Terminal1", 1), ("Terminal2", 1)]);
+    }
+
+    #[test]
+    fn cycle_does_not_leak() {
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let tail = nfa.add_terminal_node("Terminal");
+        nfa.add_child(head, NfaEdge::new_char('a'), tail);
+        nfa.add_child(tail, NfaEdge::new_char('a'), head);
+        collect_node_utils!(nfa, ['a', 'a', 'a'], vec![("Terminal", 1), ("Terminal", 3)]);
+    }
+
+    #[test]
+    fn epsilon_cycle() {
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let second = nfa.add_node();
+        let tail = nfa.add_terminal_node("Terminal");
+        nfa.add_child(head, NfaEdge::Epsilon, second);
+        nfa.add_child(second, NfaEdge::Epsilon, head);
+        nfa.add_child(second, NfaEdge::new_char('a'), tail);
+        nfa.add_child(tail, NfaEdge::Epsilon, head);
+        collect_node_utils!(nfa, ['a', 'a'], vec![("Terminal", 1), ("Terminal", 2)]);
+    }
+
+    #[test]
+    fn long_query() {
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let tail = nfa.add_terminal_node("Terminal");
+        nfa.add_child(head, NfaEdge::new_char('a'), tail);
+        nfa.add_child(tail, NfaEdge::Epsilon, head);
+        let query = vec!['a'; 100_000];
+        let res = nfa.collect_terminal(&query, 0);
+        assert_eq!(res.len(), 100_000);
+        assert_eq!(res.last(), Some(&("Terminal", 100_000)));
+    }
+
+    #[test]
+    fn start_from_middle() {
+        let mut nfa = Nfa::new();
+        let head = nfa.start();
+        let tail = nfa.add_terminal_node("Terminal");
+        nfa.add_child(head, NfaEdge::new_char('b'), tail);
+        collect_node_utils!(nfa, ['a', 'b'], vec![]);
+        assert_eq!(nfa.collect_terminal(&['a', 'b'], 1), vec![("Terminal", 2)]);
+    }
+
+    #[test]
+    fn closure_order() {
+        let mut nfa = Nfa::<()>::new();
+        let head = nfa.start();
+        let first = nfa.add_node();
+        let second = nfa.add_node();
+        let third = nfa.add_node();
+        nfa.add_child(head, NfaEdge::Epsilon, first);
+        nfa.add_child(head, NfaEdge::Epsilon, third);
+        nfa.add_child(first, NfaEdge::Epsilon, second);
+        nfa.add_child(second, NfaEdge::Epsilon, head);
+        assert_eq!(
+            nfa.epsilon_closure([head]),
+            vec![head, first, second, third]
+        );
     }
 }
 
-// use std::cmp::PartialEq;
-// use std::fmt::Debug;
-// use std::iter::IntoIterator;
-//
-// use crate::automaton::{Automaton, Content, NextNode, Node, RegexRun, State, Terminal};
-//
-// #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-// pub enum NfaState<T, C> {
-//     NfaTerminal(T),
-//     NfaContent(C),
-//     Epsilon,
-// }
-//
-// impl<T, C> State for NfaState<T, C>
-// where
-//     T: Terminal,
-//     C: Content,
-// {
-//     type Terminal = T;
-//     type Content = C;
-//
-//     fn is_content(&self) -> bool {
-//         matches!(self, Self::NfaContent(_))
-//     }
-//
-//     fn is_terminal(&self) -> bool {
-//         matches!(self, Self::NfaTerminal(_))
-//     }
-//
-//     fn terminal(&self) -> Self::Terminal {
-//         match self {
-//             Self::NfaTerminal(x) => *x,
-//             _ => panic!("this is not terminal"),
-//         }
-//     }
-//
-//     fn content(&self) -> Self::Content {
-//         match self {
-//             Self::NfaContent(x) => *x,
-//             _ => panic!("this is not content"),
-//         }
-//     }
-//
-//     fn from_content(content: Self::Content) -> Self {
-//         Self::NfaContent(content)
-//     }
-//
-//     fn from_terminal(terminal: Self::Terminal) -> Self {
-//         Self::NfaTerminal(terminal)
-//     }
-// }
-//
-// impl<T: Terminal, C: Content> Default for NfaState<T, C> {
-//     fn default() -> Self {
-//         Self::Epsilon
-//     }
-// }
-//
-// impl<T: Terminal, C: Content> NfaState<T, C> {
-//     pub fn is_epsilon(&self) -> bool {
-//         matches!(self, Self::Epsilon)
-//     }
-//
-//     pub fn from_epsilon() -> Self {
-//         Self::default()
-//     }
-// }
-//
-// #[derive(Clone, Debug, PartialEq, Eq)]
-// pub struct NfaNode<T: Terminal, C: Content> {
-//     states: Vec<(NfaState<T, C>, usize)>,
-// }
-//
-// impl<T: Terminal, C: Content> Default for NfaNode<T, C> {
-//     fn default() -> Self {
-//         Self { states: Vec::new() }
-//     }
-// }
-//
-// impl<T: Terminal, C: Content> Node for NfaNode<T, C> {
-//     type NodeState = NfaState<T, C>;
-//
-//     fn add_transition(&mut self, transision: Self::NodeState, idx: usize) {
-//         self.states.push((transision, idx));
-//     }
-//
-//     /// increment index **except** terminal
-//     fn increment_all_index(&mut self, inc: usize) {
-//         self.states
-//             .iter_mut()
-//             .filter(|(state, _)| !state.is_terminal())
-//             .for_each(|(_, idx)| *idx += inc);
-//     }
-//
-//     fn collect_terminal(&self) -> Vec<T> {
-//         self.states
-//             .iter()
-//             .filter(|(state, _)| state.is_terminal())
-//             .map(|(terminal, _)| terminal.terminal())
-//             .collect()
-//     }
-//
-//     fn collect_content(&self) -> Vec<(<Self::NodeState as State>::Content, usize)> {
-//         self.states
-//             .iter()
-//             .filter(|(state, _)| state.is_content())
-//             .map(|(content, idx)| (content.content(), *idx))
-//             .collect()
-//     }
-// }
-//
-// // impl<T: Terminal, C: Content> NfaNode<T, C> {
-// //     fn collect_epsilon_idx(&self) -> Vec<usize> {
-// //         self.states
-// //             .iter()
-// //             .filter(|(state, _)| state.is_epsilon())
-// //             .map(|(_, idx)| *idx)
-// //             .collect()
-// //     }
-// // }
-//
-// impl<T: Terminal, C: Content> NfaNode<T, C> {
-//     pub fn from_epsilon(idx: usize) -> Self {
-//         let state = NfaState::from_epsilon();
-//         let mut default = Self::default();
-//         default.add_transition(state, idx);
-//         default
-//     }
-//
-//     pub fn add_epsilon(&mut self, idx: usize) {
-//         let epsion = NfaState::default();
-//         self.add_transition(epsion, idx);
-//     }
-// }
-//
-// impl<T: Terminal, C: Content> IntoIterator for NfaNode<T, C> {
-//     type Item = (NfaState<T, C>, usize);
-//     type IntoIter = std::vec::IntoIter<Self::Item>;
-//
-//     fn into_iter(self) -> Self::IntoIter {
-//         self.states.into_iter()
-//     }
-// }
-//
-// pub type NFA<T, C> = Automaton<NfaNode<T, C>>;
-//
-// impl<T: Terminal, C: Content> NFA<T, C> {
-//     pub fn add_epsilon_idx_node(&mut self, idx: usize, direction_idx: usize) {
-//         self[idx].add_epsilon(direction_idx);
-//     }
-//
-//     /// Connect another Node's NFA to any NFA node.
-//     /// 1. add all the idx of the nfa to be connected by the original length
-//     /// 2. update the length of the original nfa
-//     /// 3. connect the first index of the nfa to the node to be connected with
-//     ///    an arbitrary NfaItem
-//     /// 4. update length of NFA
-//     pub fn concat(&mut self, source_idx: usize, cat_nfa: NFA<T, C>) {
-//         let current_len = self.len();
-//         let cat_nfa = cat_nfa.increment_all_index(current_len);
-//         self.add_epsilon_idx_node(source_idx, current_len);
-//         self.append_vec(cat_nfa);
-//     }
-//
-//     pub fn concat_tail(&mut self, cat_nfa: NFA<T, C>) {
-//         let current_len = self.len() - 1;
-//         self.concat(current_len, cat_nfa);
-//     }
-//
-//     pub fn concat_tail_n_times(&mut self, cat_nfa: NFA<T, C>, times: usize) {
-//         for _ in 0..times {
-//             self.concat_tail(cat_nfa.clone());
-//         }
-//     }
-// }
-//
-// impl<T: Terminal, C: Content> NextNode for NFA<T, C> {
-//     type InputState = NfaState<T, C>;
-//     fn next_node(&self, idx: usize, char_: char) -> Vec<usize> {
-//         self[idx]
-//             .clone()
-//             .into_iter()
-//             .filter(|(state, _)| !(state.is_content() && state.content() != char_))
-//             .map(|(_, idx_)| idx_)
-//             .collect()
-//     }
-// }
-//
-// impl<T: Terminal, C: Content> RegexRun<NfaNode<T, C>> for NFA<T, C> {}
-//
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // ----------------------------------
-// // test
-// #[allow(unused_macros)]
-// macro_rules! mock_struct {
-//     () => {
-//         use crate::regex_parser::Item;
-//         #[derive(Debug, Clone, Copy, PartialEq)]
-//         struct TestTerminal;
-//         impl Terminal for TestTerminal {}
-//         #[allow(dead_code)]
-//         type NfaTestState = NfaState<TestTerminal, Item>;
-//     };
-// }
-//
-// macro_rules! test_state {
-//     ($test_fn_name: ident, $state: expr, $test_method: ident, $assert_value: expr, $($sharp: ident)*) => {
-//         $(
-//             #[$sharp]
-//          )*
-//         #[test]
-//         fn $test_fn_name() {
-//             mock_struct!();
-//             let state: NfaTestState = $state;
-//             assert_eq!(state.$test_method(), $assert_value);
-//         }
-//     };
-// }
-//
-// test_state!(
-//     is_terminal_true,
-//     NfaTestState::NfaTerminal(TestTerminal),
-//     is_terminal,
-//     true,
-// );
-// test_state!(
-//     is_terminal_false1,
-//     NfaTestState::Epsilon,
-//     is_terminal,
-//     false,
-// );
-// test_state!(
-//     is_terminal_false2,
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     is_terminal,
-//     false,
-// );
-//
-// test_state!(
-//     is_content_false2,
-//     NfaTestState::NfaTerminal(TestTerminal),
-//     is_content,
-//     false,
-// );
-// test_state!(is_content_false1, NfaTestState::Epsilon, is_content, false,);
-// test_state!(
-//     is_content_true,
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     is_content,
-//     true,
-// );
-//
-// test_state!(
-//     is_epsilon_false2,
-//     NfaTestState::NfaTerminal(TestTerminal),
-//     is_epsilon,
-//     false,
-// );
-// test_state!(is_epsilon_true, NfaTestState::Epsilon, is_epsilon, true,);
-// test_state!(
-//     is_epsilon_false1,
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     is_epsilon,
-//     false,
-// );
-//
-// test_state!(
-//     test_terminal,
-//     NfaState::NfaTerminal(TestTerminal),
-//     terminal,
-//     TestTerminal,
-// );
-// test_state!(
-//     test_terminal_should_panic,
-//     NfaState::Epsilon,
-//     terminal,
-//     TestTerminal,
-//     should_panic
-// );
-// test_state!(
-//     test_terminal_should_panic2,
-//     NfaState::NfaContent(Item::Char('a')),
-//     terminal,
-//     TestTerminal,
-//     should_panic
-// );
-//
-// test_state!(
-//     test_content_shoud_panic2,
-//     NfaState::NfaTerminal(TestTerminal),
-//     content,
-//     Item::Char('a'),
-//     should_panic
-// );
-// test_state!(
-//     test_content_should_panic,
-//     NfaState::Epsilon,
-//     content,
-//     Item::Char('a'),
-//     should_panic
-// );
-// test_state!(
-//     test_content,
-//     NfaState::NfaContent(Item::Char('a')),
-//     content,
-//     Item::Char('a'),
-// );
-//
-// macro_rules! test_state_from {
-//     ($test_fn_name: ident, $method:ident, $assert_value: expr, $($method_args: expr)*) => {
-//         #[test]
-//             fn $test_fn_name() {
-//                 mock_struct!();
-//                 assert_eq!(NfaTestState::$method($($method_args)*), $assert_value);
-//             }
-//     }
-// }
-//
-// test_state_from!(
-//     test_from_content,
-//     from_content,
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     Item::Char('a')
-// );
-// test_state_from!(
-//     test_from_terminal,
-//     from_terminal,
-//     NfaTestState::NfaTerminal(TestTerminal),
-//     TestTerminal
-// );
-// test_state_from!(test_from_epsilon, from_epsilon, NfaTestState::Epsilon,);
-//
-// // Test For NfaNode
-//
-// #[test]
-// fn node_add_translation() {
-//     mock_struct!();
-//     let mut node = NfaNode::default();
-//     let push_state = NfaTestState::Epsilon;
-//     node.add_transition(push_state, 1);
-//     assert_eq!(
-//         node,
-//         NfaNode {
-//             states: vec![(NfaTestState::Epsilon, 1)]
-//         }
-//     );
-// }
-//
-// #[test]
-// fn node_add_content() {
-//     mock_struct!();
-//     let mut node = NfaNode::default();
-//     node.add_content(Item::Char('a'), 1);
-//     assert_eq!(
-//         node,
-//         NfaNode {
-//             states: vec![(NfaTestState::from_content(Item::Char('a')), 1)]
-//         }
-//     );
-// }
-//
-// #[test]
-// fn node_add_terminal() {
-//     mock_struct!();
-//     let mut node = NfaNode::default();
-//     node.add_terminal(TestTerminal);
-//     assert_eq!(
-//         node,
-//         NfaNode {
-//             states: vec![(NfaTestState::from_terminal(TestTerminal), 0)]
-//         }
-//     );
-// }
-//
-// #[test]
-// fn node_from_content() {
-//     mock_struct!();
-//     let node = NfaNode::from_content(Item::Char('a'), 1);
-//     assert_eq!(
-//         node,
-//         NfaNode {
-//             states: vec![(NfaTestState::from_content(Item::Char('a')), 1)]
-//         }
-//     );
-// }
-//
-// #[test]
-// fn node_add_epsilon() {
-//     mock_struct!();
-//     let mut node = NfaNode::default();
-//     node.add_epsilon(1);
-//     assert_eq!(
-//         node,
-//         NfaNode {
-//             states: vec![(NfaTestState::from_epsilon(), 1)]
-//         }
-//     );
-// }
-//
-// #[test]
-// fn node_increment_all_index() {
-//     mock_struct!();
-//     let mut node = NfaNode::default();
-//     node.add_transition(NfaTestState::Epsilon, 1);
-//     node.add_transition(NfaTestState::from_content(Item::Char('a')), 2);
-//     node.add_transition(NfaTestState::from_terminal(TestTerminal), 0);
-//     node.increment_all_index(2);
-//     let ans = NfaNode {
-//         states: vec![
-//             (NfaTestState::Epsilon, 3),
-//             (NfaTestState::NfaContent(Item::Char('a')), 4),
-//             (NfaTestState::NfaTerminal(TestTerminal), 0),
-//         ],
-//     };
-//     assert_eq!(ans, node);
-// }
-//
-// macro_rules! node_collect_test {
-//     ($test_fn_name: ident, $test_method:ident, $ans_vec: expr, $($add_transition: expr),*,, $($method_arg: expr)*) => {
-//         #[test]
-//         fn $test_fn_name() {
-//             mock_struct!();
-//             let mut node = NfaNode::default();
-//             $(
-//                 node.add_transition($add_transition, 0);
-//              )*
-//             let res = node.$test_method($($method_arg)*);
-//             assert_eq!(res, $ans_vec);
-//         }
-//     };
-// }
-//
-// node_collect_test!(
-//     node_collect_terminal,
-//     collect_terminal,
-//     vec![TestTerminal],
-//     NfaTestState::NfaTerminal(TestTerminal),
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     NfaTestState::Epsilon,,
-// );
-//
-// node_collect_test!(
-//     node_collect_terminal_null,
-//     collect_terminal,
-//     vec![],
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     NfaTestState::Epsilon,,
-// );
-//
-// node_collect_test!(
-//     node_collect_content,
-//     collect_content,
-//     vec![(Item::Char('a'), 0), (Item::Char('b'), 0)],
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     NfaTestState::NfaContent(Item::Char('b')),
-//     NfaTestState::Epsilon,,
-// );
-//
-// node_collect_test!(
-//     node_collect_content_null,
-//     collect_content,
-//     vec![],
-//     NfaTestState::Epsilon,
-//     NfaTestState::NfaTerminal(TestTerminal),
-//     NfaTestState::Epsilon,,
-// );
-//
-// node_collect_test!(
-//     node_collect_content_idx,
-//     collect_char_content_idx ,
-//     vec![0,],
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     NfaTestState::NfaContent(Item::Char('b')),
-//     NfaTestState::Epsilon,,
-//     'a'
-// );
-//
-// node_collect_test!(
-//     node_collect_content_idx_no_match_char,
-//     collect_char_content_idx ,
-//     vec![],
-//     NfaTestState::NfaContent(Item::Char('a')),
-//     NfaTestState::NfaContent(Item::Char('b')),
-//     NfaTestState::Epsilon,,
-//    'c'
-// );
-//
-// node_collect_test!(
-//     node_collect_content_idx_no_content,
-//     collect_char_content_idx ,
-//     vec![],
-//     NfaTestState::Epsilon,,
-//    'c'
-// );
-//
-// // Test For Automaton
-// #[test]
-// fn automaton_from_content() {
-//     mock_struct!();
-//     let node_1 = NfaNode::<TestTerminal, Item>::from_content(Item::Char('a'), 1);
-//     let mut nfa = NFA::default();
-//     nfa.push(node_1);
-//     nfa.push(NfaNode::default());
-//     let ans = NFA::from_content(Item::Char('a'));
-//     assert_eq!(ans, nfa);
-// }
-//
-// #[test]
-// fn concat() {
-//     mock_struct!();
-//     let mut res = NFA::<TestTerminal, Item>::from_content(Item::Char('a'));
-//     let b = NFA::<TestTerminal, Item>::from_content(Item::Char('b'));
-//     res.concat(1, b);
-//
-//     let mut ans = NFA::new();
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('a'),
-//         1,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(2));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('b'),
-//         3,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::default());
-//     assert_eq!(res, ans);
-// }
-//
-// #[test]
-// fn concat_first_node() {
-//     mock_struct!();
-//     let mut res = NFA::new();
-//     res.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('a'),
-//         1,
-//     ));
-//     res.push(NfaNode::<TestTerminal, Item>::from_epsilon(2));
-//     res.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('b'),
-//         3,
-//     ));
-//     res.push(NfaNode::<TestTerminal, Item>::from_epsilon(4));
-//     res.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('c'),
-//         5,
-//     ));
-//     res.push(NfaNode::<TestTerminal, Item>::from_terminal(TestTerminal));
-//     let concat_nfa = NFA::from_content(Item::Char('d'));
-//     res.concat(0, concat_nfa);
-//     let mut first_node = NfaNode::<TestTerminal, Item>::from_content(Item::Char('a'), 1);
-//     first_node.add_epsilon(6);
-//     let mut ans = NFA::new();
-//     ans.push(first_node);
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(2));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('b'),
-//         3,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(4));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('c'),
-//         5,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_terminal(TestTerminal));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('d'),
-//         7,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::default());
-//     assert_eq!(ans, res);
-// }
-//
-// #[test]
-// fn concat_tail() {
-//     mock_struct!();
-//     let mut res = NFA::<TestTerminal, Item>::from_content(Item::Char('a'));
-//     let node_b = NFA::<TestTerminal, Item>::from_content(Item::Char('b'));
-//     res.concat(1, node_b);
-//     let condcat_nfa = NFA::<TestTerminal, Item>::from_content(Item::Char('c'));
-//     res.concat_tail(condcat_nfa.clone());
-//     res.concat_tail(condcat_nfa.clone());
-//
-//     let mut ans = NFA::new();
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('a'),
-//         1,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(2));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('b'),
-//         3,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(4));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('c'),
-//         5,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(6));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('c'),
-//         7,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::default());
-//     assert_eq!(ans, res);
-// }
-//
-// #[test]
-// fn concat_tail_n_times() {
-//     mock_struct!();
-//     let mut res = NFA::<TestTerminal, Item>::from_content(Item::Char('a'));
-//     let node_b = NFA::<TestTerminal, Item>::from_content(Item::Char('b'));
-//     res.concat_tail(node_b);
-//     let concat_nfa = NFA::<TestTerminal, Item>::from_content(Item::Char('c'));
-//     res.concat_tail_n_times(concat_nfa, 2);
-//
-//     let mut ans = NFA::new();
-//
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('a'),
-//         1,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(2));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('b'),
-//         3,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(4));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('c'),
-//         5,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::from_epsilon(6));
-//
-//     ans.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('c'),
-//         7,
-//     ));
-//     ans.push(NfaNode::<TestTerminal, Item>::default());
-//
-//     assert_eq!(ans, res);
-// }
-//
-// #[test]
-// fn next_node_without_epsilon() {
-//     mock_struct!();
-//     let mut automaton = NFA::new();
-//     let mut node = NfaNode::<TestTerminal, Item>::from_content(Item::Char('a'), 1);
-//     node.add_content(Item::Char('b'), 100);
-//     node.add_content(Item::Char('a'), 200);
-//     automaton.push(node);
-//     let res = automaton.next_node(0, 'a');
-//     let ans = vec![1, 200];
-//     assert_eq!(res, ans);
-// }
-//
-// #[test]
-// fn next_node_with_exception() {
-//     mock_struct!();
-//     let mut automaton = NFA::new();
-//     let mut node_0 = NfaNode::<TestTerminal, Item>::from_content(Item::Char('a'), 1);
-//     node_0.add_epsilon(2);
-//     let node_1 = NfaNode::<TestTerminal, Item>::default();
-//     automaton.push(node_0);
-//     automaton.push(node_1);
-//     let mut res = automaton.next_node(0, 'a');
-//     let mut ans = vec![1, 2];
-//     res.sort();
-//     ans.sort();
-//     assert_eq!(ans, res);
-// }
-//
-// #[test]
-// fn next_node_epsilon() {
-//     mock_struct!();
-//     let mut automaton = NFA::new();
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_epsilon(1));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('a'),
-//         2,
-//     ));
-//     let res = automaton.next_node(0, 'a');
-//     let ans = vec![1];
-//     assert_eq!(res, ans);
-// }
-//
-// #[test]
-// fn next_node_terminal_content() {
-//     mock_struct!();
-//     let mut automaton = NFA::new();
-//     let mut node_0 = NfaNode::from_epsilon(2);
-//     node_0.add_content(Item::Char('a'), 1);
-//     let node_1 = NfaNode::default();
-//     let node_2 = NfaNode::from_terminal(TestTerminal);
-//     automaton.push(node_0);
-//     automaton.push(node_1);
-//     automaton.push(node_2);
-//     let mut res = automaton.next_node(0, 'a');
-//     println!("{:?}", automaton);
-//     let mut ans = vec![1, 2];
-//     res.sort();
-//     ans.sort();
-//     assert_eq!(ans, res);
-// }
-//
-// #[test]
-// fn regex_run_strait_automaton() {
-//     mock_struct!();
-//     let mut automaton = NFA::new();
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('r'),
-//         1,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('u'),
-//         2,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('s'),
-//         3,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('t'),
-//         4,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_terminal(TestTerminal));
-//     let string = "rust".to_string();
-//     let res = automaton.run(&string);
-//     let ans = vec![TestTerminal];
-//     assert_eq!(res, ans);
-// }
-//
-// #[test]
-// fn regex_run_many_path() {
-//     mock_struct!();
-//     let mut automaton = NFA::new();
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('r'),
-//         1,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Any,
-//         2,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Any,
-//         3,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_content(
-//         Item::Char('t'),
-//         4,
-//     ));
-//     automaton.push(NfaNode::<TestTerminal, Item>::from_terminal(TestTerminal));
-//     automaton.add_state_idx_node(0, NfaState::Epsilon, 4);
-//     automaton.add_state_idx_node(1, NfaState::Epsilon, 4);
-//     automaton.add_state_idx_node(2, NfaState::Epsilon, 4);
-//     automaton.add_state_idx_node(3, NfaState::Epsilon, 4);
-//     let string = "rust".to_string();
-//     let res = automaton.run(&string);
-//     let ans = vec![
-//         TestTerminal,
-//         TestTerminal,
-//         TestTerminal,
-//         TestTerminal,
-//         TestTerminal,
-//     ];
-//     assert_eq!(res, ans);
-// }
+#[cfg(test)]
+mod anchor_test {
+    use super::*;
+
+    #[test]
+    fn bol_start() {
+        let mut nfa = Nfa::new();
+        assert_eq!(nfa.bol_starts(), vec![nfa.start()]);
+        let bol = nfa.bol_start(0);
+        assert_eq!(nfa.bol_start(0), bol);
+        let end = nfa.add_terminal_node("A");
+        nfa.add_child(bol, NfaEdge::new_char('a'), end);
+        assert_eq!(nfa.collect_terminal(&['a'], 0), vec![("A", 1)]);
+        assert!(nfa.collect_terminal(&['b', 'a'], 1).is_empty());
+        assert_eq!(nfa.collect_terminal(&['\n', 'a'], 1), vec![("A", 2)]);
+    }
+
+    #[test]
+    fn eof_only() {
+        let mut nfa = Nfa::new();
+        let start = nfa.start();
+        let end = nfa.add_terminal_node("A");
+        nfa.add_child(start, NfaEdge::new_char('a'), end);
+        nfa.set_eof_only(end);
+        assert_eq!(nfa.collect_terminal(&['a'], 0), vec![("A", 1)]);
+        assert!(nfa.collect_terminal(&['a', 'a'], 0).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod trailing_test {
+    use super::*;
+
+    #[test]
+    fn token_len() {
+        assert_eq!(TrailingContext::Head(1).token_len("λ.."), 2);
+        assert_eq!(TrailingContext::Head(3).token_len("ab"), 2);
+        assert_eq!(TrailingContext::Tail(2).token_len("λ.."), 2);
+        assert_eq!(TrailingContext::Tail(1).token_len("aλ"), 1);
+        assert_eq!(TrailingContext::Tail(0).token_len("ab"), 2);
+    }
+}
@@ -0,0 +1,270 @@
+//! 正規表現の構文木
+//! パーサーは`Item`の列から`RegexAst`を作り、`regex_compiler::compile`がそれをNFAにする
+//!
+//! 構文木になった時点で、`{NAME}`は`Group`に展開済み、`(?i)`は文字クラスに反映済み、
+//! `\d`や`.`は`Class`になっている
+//! `^`と`$`はルール全体の`Concat`の両端の`Anchor`、`r/s`はルール全体の`Trailing`で表す
+//!
+//! `Display`は構文木を正規の形の正規表現に書き戻す
+//! パーサーが作った構文木なら、書き戻した正規表現をパースし直すと同じ構文木になる
+use std::fmt;
+
+use crate::char_class::{next_char, CharClass};
+use crate::regex_tokenizer::Item;
+use crate::unicode::PropertyId;
+
+/// 行頭と行末
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    /// ^
+    StartLine,
+    /// $
+    EndLine,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexAst {
+    /// 一文字
+    Literal(char),
+    /// 文字クラス 範囲内のどれか一文字
+    Class(CharClass),
+    /// 連結 空なら空文字列
+    Concat(Vec<RegexAst>),
+    /// `|`で並べた選択肢
+    Alternate(Vec<RegexAst>),
+    /// `min`回以上`max`回以下の繰り返し `max`が`None`なら上限なし
+    Repeat {
+        inner: Box<RegexAst>,
+        min: usize,
+        max: Option<usize>,
+    },
+    /// `(..)`で囲んだ部分
+    Group(Box<RegexAst>),
+    Anchor(Anchor),
+    /// `r/s`
+    Trailing(Box<RegexAst>, Box<RegexAst>),
+}
+
+impl RegexAst {
+    /// 空文字列
+    pub fn empty() -> Self {
+        Self::Concat(Vec::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::Concat(items) if items.is_empty())
+    }
+
+    /// どの一致でも読む文字数が同じなら、その文字数を返す
+    pub fn fixed_len(&self) -> Option<usize> {
+        match self {
+            Self::Literal(_) | Self::Class(_) => Some(1),
+            Self::Anchor(_) => Some(0),
+            Self::Group(inner) => inner.fixed_len(),
+            Self::Concat(items) => items
+                .iter()
+                .try_fold(0usize, |len, item| len.checked_add(item.fixed_len()?)),
+            Self::Alternate(branches) => {
+                let (first, rest) = branches.split_first()?;
+                let len = first.fixed_len()?;
+                rest.iter()
+                    .all(|branch| branch.fixed_len() == Some(len))
+                    .then_some(len)
+            }
+            Self::Repeat { inner, min, max } if *max == Some(*min) => {
+                inner.fixed_len()?.checked_mul(*min)
+            }
+            Self::Repeat { .. } | Self::Trailing(..) => None,
+        }
+    }
+}
+
+/// そのまま書いても読める文字か
+/// 制御文字、空白以外の空白類、私用領域は`\n`や`\u{..}`で書く
+fn is_printable(c: char) -> bool {
+    !c.is_control()
+        && (c == ' ' || !c.is_whitespace())
+        && !matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..=char::MAX)
+}
+
+/// そのまま書けない文字をエスケープして書く
+fn write_unprintable(f: &mut fmt::Formatter<'_>, c: char) -> fmt::Result {
+    match c {
+        '\t' => f.write_str(r"\t"),
+        '\n' => f.write_str(r"\n"),
+        '\r' => f.write_str(r"\r"),
+        '\u{B}' => f.write_str(r"\v"),
+        '\u{C}' => f.write_str(r"\f"),
+        c => write!(f, r"\u{{{:x}}}", c as u32),
+    }
+}
+
+/// 文字クラスの外の一文字
+/// 特殊記号は`\.`のようにエスケープした方のトークンで書き、`"`, `/`, `$`もエスケープする
+fn write_literal(f: &mut fmt::Formatter<'_>, c: char) -> fmt::Result {
+    match c {
+        c if !is_printable(c) => write_unprintable(f, c),
+        '"' | '/' | '$' => write!(f, "\\{}", c),
+        // `\-`と書く必要があるのは文字クラスの中だけ
+        '-' => f.write_str("-"),
+        // `Reference`以外は必ず書ける
+        c => f.write_str(&Item::from(c).to_pattern().unwrap()),
+    }
+}
+
+/// 文字クラスの中の一文字
+/// `]`, `\`, `-`と、クラスの先頭の`^`だけエスケープする
+fn write_class_char(f: &mut fmt::Formatter<'_>, c: char, first: bool) -> fmt::Result {
+    match c {
+        c if !is_printable(c) => write_unprintable(f, c),
+        ']' | '\\' | '-' => write!(f, "\\{}", c),
+        '^' if first => f.write_str(r"\^"),
+        c => write!(f, "{}", c),
+    }
+}
+
+/// 文字クラス
+/// `.`, `\d`のような省略形, `\p{..}`のどれでも書けなければ、
+/// `[..]`と`[^..]`のうち範囲の少ない方で書く 一文字でも`[a]`のように括弧で囲む
+fn write_class(f: &mut fmt::Formatter<'_>, class: &CharClass) -> fmt::Result {
+    if *class == CharClass::any() {
+        return f.write_str(".");
+    }
+    let shorthands = [
+        Item::SmallD,
+        Item::LargeD,
+        Item::SmallS,
+        Item::LargeS,
+        Item::SmallW,
+        Item::LargeW,
+    ];
+    let negated = class.negate();
+    // 空のクラスは`\p{Surrogate}`などと同じになるので、表は探さない
+    // `[]`とも書けないので、反転して`[^\u{0}-\u{10ffff}]`と書く
+    if !class.is_empty() {
+        let item = shorthands
+            .into_iter()
+            .find(|item| item.shorthand_class().as_ref() == Some(class))
+            .or_else(|| PropertyId::find(class).map(Item::SmallP))
+            .or_else(|| PropertyId::find(&negated).map(Item::LargeP));
+        if let Some(item) = item {
+            return f.write_str(&item.to_pattern().unwrap());
+        }
+    }
+
+    let negate = class.is_empty() || negated.ranges().len() < class.ranges().len();
+    let ranges = if negate {
+        f.write_str("[^")?;
+        negated.ranges()
+    } else {
+        f.write_str("[")?;
+        class.ranges()
+    };
+    for (idx, &(start, end)) in ranges.iter().enumerate() {
+        write_class_char(f, start, idx == 0 && !negate)?;
+        if start == end {
+            continue;
+        }
+        // 隣り合う二文字は`ab`、三文字以上は`a-c`と書く
+        if next_char(start) != Some(end) {
+            f.write_str("-")?;
+        }
+        write_class_char(f, end, false)?;
+    }
+    f.write_str("]")
+}
+
+impl fmt::Display for RegexAst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Literal(c) => write_literal(f, *c),
+            Self::Class(class) => write_class(f, class),
+            Self::Concat(items) => items.iter().try_for_each(|item| match item {
+                Self::Alternate(_) => write!(f, "({})", item),
+                item => write!(f, "{}", item),
+            }),
+            Self::Alternate(branches) => {
+                for (idx, branch) in branches.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str("|")?;
+                    }
+                    write!(f, "{}", branch)?;
+                }
+                Ok(())
+            }
+            Self::Repeat { inner, min, max } => {
+                match **inner {
+                    Self::Literal(_) | Self::Class(_) | Self::Group(_) | Self::Repeat { .. } => {
+                        write!(f, "{}", inner)?
+                    }
+                    _ => write!(f, "({})", inner)?,
+                }
+                match (*min, *max) {
+                    (0, None) => f.write_str("*"),
+                    (1, None) => f.write_str("+"),
+                    (0, Some(1)) => f.write_str("?"),
+                    (min, None) => write!(f, "{{{},}}", min),
+                    (min, Some(max)) if min == max => write!(f, "{{{}}}", min),
+                    (min, Some(max)) => write!(f, "{{{},{}}}", min, max),
+                }
+            }
+            Self::Group(inner) => write!(f, "({})", inner),
+            Self::Anchor(Anchor::StartLine) => f.write_str("^"),
+            Self::Anchor(Anchor::EndLine) => f.write_str("$"),
+            Self::Trailing(head, tail) => write!(f, "{}/{}", head, tail),
+        }
+    }
+}
+
+#[cfg(test)]
+mod regex_ast_test {
+    use super::*;
+
+    fn repeat(inner: RegexAst, min: usize, max: Option<usize>) -> RegexAst {
+        RegexAst::Repeat {
+            inner: Box::new(inner),
+            min,
+            max,
+        }
+    }
+
+    #[test]
+    fn fixed_len() {
+        let ab = RegexAst::Concat(vec![RegexAst::Literal('a'), RegexAst::Literal('b')]);
+        assert_eq!(ab.fixed_len(), Some(2));
+        assert_eq!(RegexAst::empty().fixed_len(), Some(0));
+        let class = RegexAst::Class(CharClass::any());
+        let alternate = RegexAst::Alternate(vec![ab.clone(), repeat(class, 2, Some(2))]);
+        assert_eq!(alternate.fixed_len(), Some(2));
+        let alternate = RegexAst::Alternate(vec![ab.clone(), RegexAst::Literal('c')]);
+        assert_eq!(alternate.fixed_len(), None);
+        assert_eq!(repeat(ab.clone(), 0, None).fixed_len(), None);
+        assert_eq!(repeat(ab, 1, Some(2)).fixed_len(), None);
+    }
+
+    #[test]
+    fn display() {
+        let class =
+            |ranges: &[(char, char)]| RegexAst::Class(CharClass::from_ranges(ranges.to_vec()));
+        assert_eq!(class(&[('a', 'c'), ('x', 'y')]).to_string(), "[a-cxy]");
+        assert_eq!(class(&[('a', 'a')]).to_string(), "[a]");
+        assert_eq!(class(&[('^', '^'), ('-', '-')]).to_string(), r"[\-^]");
+        assert_eq!(class(&[('^', '^')]).to_string(), r"[\^]");
+        assert_eq!(class(&[('0', '9')]).to_string(), r"\d");
+        assert_eq!(
+            class(&[('\0', '@'), ('[', char::MAX)]).to_string(),
+            "[^A-Z]"
+        );
+        assert_eq!(class(&[]).to_string(), r"[^\u{0}-\u{10ffff}]");
+        let literals = "a.\"/$\n-".chars().map(RegexAst::Literal).collect();
+        assert_eq!(RegexAst::Concat(literals).to_string(), r#"a\.\"\/\$\n-"#);
+        // 構文木を手で組んだ時は、必要な括弧を補う
+        let alternate = RegexAst::Alternate(vec![RegexAst::Literal('a'), RegexAst::empty()]);
+        assert_eq!(alternate.to_string(), "a|");
+        assert_eq!(repeat(alternate.clone(), 2, None).to_string(), "(a|){2,}");
+        let concat = RegexAst::Concat(vec![alternate, RegexAst::Literal('b')]);
+        assert_eq!(concat.to_string(), "(a|)b");
+        let trailing = RegexAst::Trailing(Box::new(RegexAst::Literal('a')), Box::new(concat));
+        assert_eq!(trailing.to_string(), "a/(a|)b");
+    }
+}
@@ -0,0 +1,268 @@
+//! 構文木からNFAを作る
+//! Thompson構成法で、構文木の節ごとに (開始状態, 受理状態) の部分グラフを組み立てる
+//!
+//! `Anchor`と`Trailing`はルール全体の形でだけ意味を持つので、
+//! `compile_into`がルールの両端から取り外してから中身を組み立てる
+use crate::nfa::{Nfa, NfaEdge, StateId, TrailingContext};
+use crate::regex_ast::{Anchor, RegexAst};
+use crate::regex_error::RegexErrorKind;
+
+/// NFAの部分グラフを表す (開始状態, 受理状態) の組
+pub type NfaPair = (StateId, StateId);
+
+/// `edge`一本だけで繋がった部分グラフ
+fn single_edge<T>(nfa: &mut Nfa<T>, edge: NfaEdge) -> NfaPair {
+    let start = nfa.add_node();
+    let end = nfa.add_node();
+    nfa.add_child(start, edge, end);
+    (start, end)
+}
+
+/// 何も消費せずに受理する部分グラフ
+fn epsilon<T>(nfa: &mut Nfa<T>) -> NfaPair {
+    single_edge(nfa, NfaEdge::new_epsilon())
+}
+
+/// `items`を順に繋いだ部分グラフ 空なら空文字列を受理する
+fn concat<T: Clone>(nfa: &mut Nfa<T>, items: &[RegexAst]) -> Result<NfaPair, RegexErrorKind> {
+    let Some((first, rest)) = items.split_first() else {
+        return Ok(epsilon(nfa));
+    };
+    let (start, mut end) = node(nfa, first)?;
+    for item in rest {
+        let (s, e) = node(nfa, item)?;
+        nfa.add_child(end, NfaEdge::new_epsilon(), s);
+        end = e;
+    }
+    Ok((start, end))
+}
+
+/// `min`回以上`max`回以下の繰り返し
+// Example
+// { 2, 3 }
+//                         min番目の境界
+//                              |
+//                             \/
+// s -ε- NFA -ε- . -ε- NFA -ε- . -ε- NFA -ε- . -ε- e
+//                             |                  /\
+//                             --------------------
+//                                      ε
+// { 2, }
+// s -ε- NFA -ε- . -ε- NFA -ε- e
+//                     /\   |
+//                      -----
+//                        ε
+// {2}
+// s -ε- NFA -ε- . -ε- NFA -ε- e
+//
+// 連結するNFAの数は max が Some -> max, None -> min (ただし最低1)
+fn repeat<T: Clone>(
+    nfa: &mut Nfa<T>,
+    inner: &RegexAst,
+    min: usize,
+    max: Option<usize>,
+) -> Result<NfaPair, RegexErrorKind> {
+    let num_nfa = max.unwrap_or_else(|| usize::max(min, 1));
+    if num_nfa == 0 {
+        return Ok(epsilon(nfa));
+    }
+    let pair = node(nfa, inner)?;
+    // 連結する前に複製しないと、連結済みの状態まで複製されてしまう
+    let mut fragments = (1..num_nfa)
+        .map(|_| nfa.duplicate(pair.0, pair.1))
+        .collect::<Vec<_>>();
+    fragments.insert(0, pair);
+
+    let (start, end) = (nfa.add_node(), nfa.add_node());
+    let mut current = start;
+    for (idx, (s, e)) in fragments.into_iter().enumerate() {
+        if idx >= min {
+            nfa.add_child(current, NfaEdge::new_epsilon(), end);
+        }
+        nfa.add_child(current, NfaEdge::new_epsilon(), s);
+        if max.is_none() && idx == num_nfa - 1 {
+            nfa.add_child(e, NfaEdge::new_epsilon(), s);
+        }
+        current = e;
+    }
+    nfa.add_child(current, NfaEdge::new_epsilon(), end);
+    Ok((start, end))
+}
+
+/// 構文木の節一つ分の部分グラフ
+/// `Anchor`と`Trailing`はルールの端以外には置けない
+fn node<T: Clone>(nfa: &mut Nfa<T>, ast: &RegexAst) -> Result<NfaPair, RegexErrorKind> {
+    match ast {
+        RegexAst::Literal(c) => Ok(single_edge(nfa, NfaEdge::new_char(*c))),
+        RegexAst::Class(class) => Ok(single_edge(nfa, NfaEdge::new_class(class.clone()))),
+        RegexAst::Concat(items) => concat(nfa, items),
+        RegexAst::Alternate(branches) => {
+            let (start, end) = (nfa.add_node(), nfa.add_node());
+            for branch in branches {
+                let (s, e) = node(nfa, branch)?;
+                nfa.add_child(start, NfaEdge::new_epsilon(), s);
+                nfa.add_child(e, NfaEdge::new_epsilon(), end);
+            }
+            Ok((start, end))
+        }
+        RegexAst::Repeat { inner, min, max } => repeat(nfa, inner, *min, *max),
+        RegexAst::Group(inner) => node(nfa, inner),
+        RegexAst::Anchor(_) => Err(RegexErrorKind::MisplacedAnchor),
+        RegexAst::Trailing(..) => Err(RegexErrorKind::MisplacedTrailingContext),
+    }
+}
+
+/// ルール全体の構文木を`nfa`に追加し、`starts`の番号の開始状態から繋ぐ
+/// `^`のルールは行頭の開始状態から繋ぐ
+/// `r$`は`r/\n`と、入力の終わりでだけ受理する`r`の二つの受理状態を持つ
+pub fn compile_into<T: Clone>(
+    nfa: &mut Nfa<T>,
+    ast: &RegexAst,
+    terminal: T,
+    starts: &[usize],
+) -> Result<NfaPair, RegexErrorKind> {
+    let mut items = match ast {
+        RegexAst::Concat(items) => items.as_slice(),
+        ast => std::slice::from_ref(ast),
+    };
+    let bol = items.first() == Some(&RegexAst::Anchor(Anchor::StartLine));
+    if bol {
+        items = &items[1..];
+    }
+    let eol = items.last() == Some(&RegexAst::Anchor(Anchor::EndLine));
+    if eol {
+        items = &items[..items.len() - 1];
+    }
+
+    let mut eof = None;
+    let ((start, end), trailing) = match items {
+        [RegexAst::Trailing(_, _)] if eol => return Err(RegexErrorKind::MisplacedAnchor),
+        [RegexAst::Trailing(head, tail)] => {
+            let trailing = match (head.fixed_len(), tail.fixed_len()) {
+                (Some(len), _) => TrailingContext::Head(len),
+                (None, Some(len)) => TrailingContext::Tail(len),
+                (None, None) => return Err(RegexErrorKind::VariableTrailingContext),
+            };
+            let (start, end) = node(nfa, head)?;
+            let (tail_start, tail_end) = node(nfa, tail)?;
+            nfa.add_child(end, NfaEdge::new_epsilon(), tail_start);
+            ((start, tail_end), Some(trailing))
+        }
+        items if eol => {
+            let (start, end) = concat(nfa, items)?;
+            let (newline, tail_end) = single_edge(nfa, NfaEdge::new_char('\n'));
+            nfa.add_child(end, NfaEdge::new_epsilon(), newline);
+            eof = Some(end);
+            ((start, tail_end), Some(TrailingContext::Tail(1)))
+        }
+        items => (concat(nfa, items)?, None),
+    };
+
+    nfa.set_terminal(end, terminal.clone());
+    if let Some(trailing) = trailing {
+        nfa.set_trailing(end, trailing);
+    }
+    if let Some(eof) = eof {
+        nfa.set_terminal(eof, terminal);
+        nfa.set_eof_only(eof);
+    }
+    for &idx in starts {
+        let from = if bol {
+            nfa.bol_start(idx)
+        } else {
+            nfa.starts()[idx]
+        };
+        nfa.add_child(from, NfaEdge::new_epsilon(), start);
+    }
+    Ok((start, end))
+}
+
+/// ルール一つ分のNFAを作る 受理状態には`terminal`を設定する
+pub fn compile<T: Clone>(ast: &RegexAst, terminal: T) -> Result<Nfa<T>, RegexErrorKind> {
+    let mut nfa = Nfa::new();
+    compile_into(&mut nfa, ast, terminal, &[0])?;
+    Ok(nfa)
+}
+
+#[cfg(test)]
+pub(crate) mod regex_compiler_test {
+    use super::*;
+    use crate::char_class::CharClass;
+
+    /// `ast`をNFAにして`query`の先頭から走らせ、受理した位置を昇順で返す
+    pub(crate) fn positions(ast: &RegexAst, query: &str) -> Vec<usize> {
+        let nfa = compile(ast, ()).unwrap();
+        let query = query.chars().collect::<Vec<_>>();
+        let mut res = nfa
+            .collect_terminal(&query, 0)
+            .into_iter()
+            .map(|(_, idx)| idx)
+            .collect::<Vec<_>>();
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+
+    #[test]
+    fn hand_built() {
+        // (a|[0-9])+
+        let ast = RegexAst::Repeat {
+            inner: Box::new(RegexAst::Group(Box::new(RegexAst::Alternate(vec![
+                RegexAst::Literal('a'),
+                RegexAst::Class(CharClass::from_ranges([('0', '9')])),
+            ])))),
+            min: 1,
+            max: None,
+        };
+        assert_eq!(positions(&ast, "a1b"), vec![1, 2]);
+        assert_eq!(positions(&RegexAst::empty(), "a"), vec![0]);
+    }
+
+    #[test]
+    fn anchors() {
+        let ast = RegexAst::Concat(vec![
+            RegexAst::Anchor(Anchor::StartLine),
+            RegexAst::Literal('a'),
+            RegexAst::Anchor(Anchor::EndLine),
+        ]);
+        assert_eq!(positions(&ast, "a"), vec![1]);
+        assert_eq!(positions(&ast, "a\n"), vec![2]);
+        assert_eq!(positions(&ast, "ab"), vec![]);
+    }
+
+    #[test]
+    fn misplaced() {
+        let ast = RegexAst::Concat(vec![
+            RegexAst::Literal('a'),
+            RegexAst::Anchor(Anchor::StartLine),
+        ]);
+        assert_eq!(
+            compile(&ast, ()).unwrap_err(),
+            RegexErrorKind::MisplacedAnchor
+        );
+        let trailing = RegexAst::Trailing(
+            Box::new(RegexAst::Literal('a')),
+            Box::new(RegexAst::Literal('b')),
+        );
+        let ast = RegexAst::Group(Box::new(trailing.clone()));
+        assert_eq!(
+            compile(&ast, ()).unwrap_err(),
+            RegexErrorKind::MisplacedTrailingContext
+        );
+        assert!(compile(&trailing, ()).is_ok());
+    }
+
+    #[test]
+    fn variable_trailing_context() {
+        let plus = |c| RegexAst::Repeat {
+            inner: Box::new(RegexAst::Literal(c)),
+            min: 1,
+            max: None,
+        };
+        let ast = RegexAst::Trailing(Box::new(plus('a')), Box::new(plus('b')));
+        assert_eq!(
+            compile(&ast, ()).unwrap_err(),
+            RegexErrorKind::VariableTrailingContext
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! 正規表現のトークナイズ・パース時のエラー
+//! どこで何が起きたかを、元の正規表現と位置付きで返す
+use std::error::Error;
+use std::fmt;
+
+use crate::regex_parser::MAX_REPETITION;
+
+/// エラーの種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexErrorKind {
+    /// `\`の後に続けられない文字が来た (例: `\q`)
+    InvalidEscape(char),
+    /// 正規表現が`\`で終わっている
+    TrailingBackslash,
+    /// `\x`の後に16進数が2桁続かない
+    InvalidHexEscape,
+    /// `\u{...}`の形になっていない、または16進数が7桁以上ある
+    InvalidUnicodeEscape,
+    /// サロゲートや`\u{10FFFF}`より大きいなど、文字として使えない値
+    InvalidCodePoint(u32),
+    /// `\p`の後に`{名前}`か一文字の名前が続かない
+    InvalidPropertyEscape,
+    /// `\p{..}`の名前の表が無い
+    UnknownProperty(String),
+    /// `"`に対応する`"`が無い
+    UnclosedQuote,
+    /// `(`に対応する`)`が無い
+    UnclosedGroup,
+    /// `(?x)`のように、`(?i)`, `(?-i)`, `(?i:..)`以外の書き方をした
+    InvalidFlag,
+    /// `)`に対応する`(`が無い
+    UnopenedGroup,
+    /// `[`に対応する`]`が無い
+    UnclosedClass,
+    /// `[z-a]`のように範囲の順番が逆
+    InvalidClassRange(char, char),
+    /// 文字クラスの範囲の端に使えないトークン
+    InvalidClassRangeEnd,
+    /// `[:foo:]`のように、POSIXクラスの名前が無い
+    UnknownPosixClass(String),
+    /// `{NAME}`の名前が定義されていない
+    UndefinedName(String),
+    /// `{NAME}`を展開すると、その定義自身をまた参照する
+    RecursiveDefinition(String),
+    /// `r/s`の`/`が括弧や定義の中にあるか、二つ以上ある
+    MisplacedTrailingContext,
+    /// `r/s`の`r`と`s`のどちらも長さが決まっていない
+    VariableTrailingContext,
+    /// `^`か`$`が括弧や定義の中にあるか、`$`を`r/s`と一緒に使った
+    MisplacedAnchor,
+    /// `{`に対応する`}`が無い
+    UnclosedRepetition,
+    /// `{,3}`のように最小回数が無い
+    MissingRepetitionMin,
+    /// `{2x}`のように繰り返し回数が数字でない
+    InvalidRepetition,
+    /// 繰り返し回数が`MAX_REPETITION`より大きい
+    RepetitionTooLarge,
+    /// `{5,4}`のように max < min
+    InvalidRepetitionRange(usize, usize),
+    /// `*a`のように繰り返す対象が無い
+    NothingToRepeat,
+    /// 対応する開き括弧の無い`]`や`}`
+    UnexpectedChar(char),
+}
+
+impl fmt::Display for RegexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEscape(c) => write!(f, "invalid escape sequence `\\{}`", c),
+            Self::TrailingBackslash => write!(f, "backslash cannot end a regular expression"),
+            Self::InvalidHexEscape => write!(f, "`\\x` must be followed by two hex digits"),
+            Self::InvalidUnicodeEscape => {
+                write!(f, "`\\u` must be followed by 1 to 6 hex digits in braces")
+            }
+            Self::InvalidCodePoint(code) => write!(f, "invalid code point {:#X}", code),
+            Self::InvalidPropertyEscape => {
+                write!(f, "`\\p` must be followed by a name in braces")
+            }
+            Self::UnknownProperty(name) => write!(f, "unknown unicode property `{}`", name),
+            Self::UnclosedQuote => write!(f, "unclosed quote"),
+            Self::UnclosedGroup => write!(f, "unclosed parenthesis"),
+            Self::InvalidFlag => write!(f, "only `(?i)`, `(?-i)` and `(?i:...)` are supported"),
+            Self::UnopenedGroup => write!(f, "unopened parenthesis"),
+            Self::UnclosedClass => write!(f, "unclosed character class"),
+            Self::InvalidClassRange(start, end) => {
+                write!(
+                    f,
+                    "range `{}-{}` is out of order in character class",
+                    start, end
+                )
+            }
+            Self::InvalidClassRangeEnd => write!(f, "invalid range end in character class"),
+            Self::UnknownPosixClass(name) => write!(f, "unknown POSIX class `[:{}:]`", name),
+            Self::UndefinedName(name) => write!(f, "`{{{}}}` is not defined", name),
+            Self::RecursiveDefinition(name) => {
+                write!(f, "definition `{{{}}}` refers to itself", name)
+            }
+            Self::MisplacedTrailingContext => {
+                write!(
+                    f,
+                    "trailing context `/` is only allowed once at the top level"
+                )
+            }
+            Self::VariableTrailingContext => {
+                write!(
+                    f,
+                    "either side of trailing context `/` must have a fixed length"
+                )
+            }
+            Self::MisplacedAnchor => {
+                write!(f, "`^` and `$` are only allowed at the ends of a rule")
+            }
+            Self::UnclosedRepetition => write!(f, "unclosed repetition"),
+            Self::MissingRepetitionMin => write!(f, "repetition needs a minimum count"),
+            Self::InvalidRepetition => write!(f, "invalid repetition"),
+            Self::RepetitionTooLarge => {
+                write!(f, "repetition count is larger than {}", MAX_REPETITION)
+            }
+            Self::InvalidRepetitionRange(min, max) => {
+                write!(f, "repetition max {} is smaller than min {}", max, min)
+            }
+            Self::NothingToRepeat => write!(f, "repetition operator has nothing to repeat"),
+            Self::UnexpectedChar(c) => write!(f, "unexpected `{}`", c),
+        }
+    }
+}
+
+/// 正規表現のエラー
+/// `position`は正規表現の先頭から数えた文字単位の位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    kind: RegexErrorKind,
+    pattern: String,
+    position: usize,
+}
+
+impl RegexError {
+    pub fn new(kind: RegexErrorKind, pattern: &str, position: usize) -> Self {
+        Self {
+            kind,
+            pattern: pattern.to_string(),
+            position,
+        }
+    }
+
+    pub fn kind(&self) -> &RegexErrorKind {
+        &self.kind
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 文字単位の位置
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// バイト単位の位置
+    pub fn byte_offset(&self) -> usize {
+        self.pattern
+            .char_indices()
+            .nth(self.position)
+            .map_or(self.pattern.len(), |(offset, _)| offset)
+    }
+}
+
+/// ```text
+/// error: invalid escape sequence `\q`
+///   |
+///   | ab\q
+///   |   ^
+/// ```
+/// のようにエラー箇所を`^`で指して表示する
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "error: {}", self.kind)?;
+        writeln!(f, "  |")?;
+        writeln!(f, "  | {}", self.pattern)?;
+        write!(f, "  | {}^", " ".repeat(self.position))
+    }
+}
+
+impl Error for RegexError {}
+
+#[cfg(test)]
+mod regex_error_test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let error = RegexError::new(RegexErrorKind::InvalidEscape('q'), r"ab\q", 2);
+        let ans = "error: invalid escape sequence `\\q`\n  |\n  | ab\\q\n  |   ^";
+        assert_eq!(error.to_string(), ans);
+    }
+
+    #[test]
+    fn display_end_of_pattern() {
+        let error = RegexError::new(RegexErrorKind::UnclosedGroup, "(ab", 3);
+        assert!(error.to_string().ends_with("  | (ab\n  |    ^"));
+    }
+
+    #[test]
+    fn byte_offset() {
+        let error = RegexError::new(RegexErrorKind::UnclosedClass, "λ→[a", 2);
+        assert_eq!(error.position(), 2);
+        assert_eq!(error.byte_offset(), 5);
+        let error = RegexError::new(RegexErrorKind::UnclosedClass, "λ→[a", 4);
+        assert_eq!(error.byte_offset(), 7);
+    }
+}
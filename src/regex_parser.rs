@@ -1,49 +1,623 @@
+//! 正規表現のパーサー
+//! トークナイザーが出力した`Item`の列を再帰下降で読み、構文木(`RegexAst`)を作る
+//! NFAは`regex_compiler`で構文木から組み立てる
+//!
 //! 作りたいルールの一覧
 //! 1. or a|b -> aとb両方とも受理する
-//! 2. 括りだし {adfd} -> adfdを受理する
+//! 2. 括りだし (adfd) -> adfdを受理する
 //! 3. 回数指定繰り返し -> {2, 3}とか*など repに対応するもの
+//!
 //! 文法の優先順位を考える
-//! 一番低い文法をcharsとする
-//! expr = (word { ( "|" world ) | rep }?)*
-//! word = ( ors | Alphabet ) * | "(" expr ")"
-//! ors = [Alphabet * ]
+//! 一番低い文法をchars(Alphabet)とする
+//! ```text
+//! rule     = "^"? expr ( "/" expr | "$" )?
+//! expr     = concat ( "|" concat )*
+//! concat   = rep*
+//! rep      = word rep_op*
+//! rep_op   = "*" | "+" | "?" | "{" Digit+ ( "," Digit* )? "}"
+//! word     = Alphabet | "(" expr ")" | flags | class | "{" Name "}"
+//! flags    = "(?" "-"? "i" ( ")" | ":" expr ")" )
+//! class    = "[" "^"? ( posix | Alphabet ( "-" Alphabet )? )* "]"
+//! posix    = "[:" "^"? Name ":]"
 //! Alphabet = a-z | A-Z | 0 - 9 | 記号
+//! ```
+//!
+//! `{Name}`は`[definitions]`の定義を括弧で囲んだものとして展開する
+//! `r/s`は`rs`に一致した時に`r`の部分だけをトークンにする
+//! どこで区切るかは`r`か`s`の長さで決めるので、どちらかは長さが決まっている必要がある
+//! `^r`は行頭でだけ、`r$`は改行の前か入力の終わりでだけ一致する
+//!
+//! 要素が一つだけの連結や選択は、その要素そのものにする
+//!
 //! ユニットテストはしたいけど、結合テストメインで行う
-//! TODO ユニットテスト
-use std::fmt::Debug;
-use std::rc::Rc;
-use std::cell::RefCell;
-
-use crate::regex_tokenizer::{Item, RegexTokenIter};
-use crate::nfa::{NfaEdge, NfaNode};
+use crate::char_class::CharClass;
+use crate::nfa::Nfa;
+use crate::regex_ast::{Anchor, RegexAst};
+use crate::regex_compiler::{compile_into, NfaPair};
+use crate::regex_error::{RegexError, RegexErrorKind};
+use crate::regex_simplifier::simplify;
+use crate::regex_tokenizer::{Item, Regex, RegexTokenIter};
 
 macro_rules! not_alphabet_set {
     () => {
         [
-        Item::OneOrMore, 
-        Item::Any, 
-        Item::SomeTime, 
-        Item::Or, 
-        Item::ZeroOrOne, 
-        Item::BracketL, 
-        Item::BracketR, 
-        Item::CurryL, 
-        Item::CurryR, 
-        Item::SquareL, 
-        Item::SquareR
+            Item::OneOrMore,
+            Item::SomeTime,
+            Item::Or,
+            Item::ZeroOrOne,
+            Item::BracketL,
+            Item::BracketR,
+            Item::CurryL,
+            Item::CurryR,
+            Item::SquareL,
+            Item::SquareR,
+            Item::Slash,
+            Item::Bol,
+            Item::Eol,
         ]
+    };
+}
+
+/// `(?i)`の中なら、大文字小文字を区別しない文字クラスにする
+fn fold_case(iter: &RegexTokenIter, ast: RegexAst) -> RegexAst {
+    if !iter.case_insensitive() {
+        return ast;
+    }
+    let class = match &ast {
+        RegexAst::Literal(c) => CharClass::from_char(*c),
+        RegexAst::Class(class) => class.clone(),
+        _ => return ast,
+    };
+    let folded = class.case_fold();
+    if folded == class {
+        ast
+    } else {
+        RegexAst::Class(folded)
     }
 }
 
-pub fn alphabet<T: Clone + Debug>(iter: &mut RegexTokenIter) -> Option<(Rc<RefCell<NfaNode<T>>>, Rc<RefCell<NfaNode<T>>>)> {
-    let next_token = iter.next()?;
+/// 文字一つ分
+/// `\d`のような省略形と`.`は文字クラスにする
+pub fn alphabet(iter: &mut RegexTokenIter) -> Option<RegexAst> {
+    let next_token = iter.peek()?;
     if not_alphabet_set!().contains(&next_token) {
-        None
+        return None;
+    }
+    iter.next();
+    let ast = match (next_token, next_token.shorthand_class()) {
+        (_, Some(class)) => RegexAst::Class(class),
+        (Item::Any, None) => RegexAst::Class(CharClass::any()),
+        // shorthand_classかto_charのどちらかは必ずある
+        (item, None) => RegexAst::Literal(item.to_char().unwrap()),
+    };
+    Some(fold_case(iter, ast))
+}
+
+/// posix = "[:" "^"? Name ":]"
+/// 先頭の"["は読み終わっている前提
+/// `[:`で始まらないか`:]`で閉じていなければ、何も読まずに`None`を返す
+fn posix_class(iter: &mut RegexTokenIter) -> Result<Option<CharClass>, RegexError> {
+    let position = iter.prev_offset();
+    let start = iter.clone();
+    if iter.next() != Some(Item::Char(':')) {
+        *iter = start;
+        return Ok(None);
+    }
+    let mut name = String::new();
+    loop {
+        match iter.next() {
+            Some(Item::Char(':')) if iter.peek() == Some(Item::SquareR) => {
+                iter.next();
+                break;
+            }
+            Some(Item::Char(c)) if c.is_ascii_alphabetic() || (c == '^' && name.is_empty()) => {
+                name.push(c)
+            }
+            _ => {
+                *iter = start;
+                return Ok(None);
+            }
+        }
+    }
+    let (negated, name) = match name.strip_prefix('^') {
+        Some(name) => (true, name),
+        None => (false, name.as_str()),
+    };
+    match CharClass::posix(name) {
+        Some(class) if negated => Ok(Some(class.negate())),
+        Some(class) => Ok(Some(class)),
+        None => {
+            let kind = RegexErrorKind::UnknownPosixClass(name.to_string());
+            Err(iter.error(kind, position))
+        }
+    }
+}
+
+/// class = "[" "^"? ( posix | Alphabet ( "-" Alphabet )? )* "]"
+/// 先頭の"["は読み終わっている前提
+/// "-"はクラスの最後に置くか`\-`と書くと文字として扱う
+/// `[]a]`のように先頭に置いた"]"は、トークナイザーが文字にしている
+pub fn char_class(iter: &mut RegexTokenIter) -> Result<CharClass, RegexError> {
+    // エラーは開き括弧"["の位置を指す
+    let open_position = iter.prev_offset();
+    let unclosed = |iter: &RegexTokenIter| iter.error(RegexErrorKind::UnclosedClass, open_position);
+
+    let negated = iter.peek() == Some(Item::Char('^'));
+    if negated {
+        iter.next();
+    }
+
+    let mut class = CharClass::new();
+    loop {
+        let item = iter.next().ok_or_else(|| unclosed(iter))?;
+        if item == Item::SquareR {
+            break;
+        }
+        if let Some(shorthand) = item.shorthand_class() {
+            class.union(&shorthand);
+            continue;
+        }
+        // クラスの中の`{NAME}`は展開せず、そのままの文字として扱う
+        if let Item::Reference(idx) = item {
+            let name = format!("{{{}}}", iter.reference_name(idx));
+            name.chars().for_each(|c| class.push(c, c));
+            continue;
+        }
+        if item == Item::SquareL {
+            if let Some(posix) = posix_class(iter)? {
+                class.union(&posix);
+                continue;
+            }
+        }
+        // SquareR以外はto_charで文字に戻せる
+        let start = item.to_char().unwrap();
+        if iter.peek() != Some(Item::Char('-')) {
+            class.push(start, start);
+            continue;
+        }
+        let range_position = iter.prev_offset();
+        iter.next();
+        let end_position = iter.offset();
+        match iter.next().ok_or_else(|| unclosed(iter))? {
+            // "-"で終わる場合は文字として扱う
+            Item::SquareR => {
+                class.push(start, start);
+                class.push('-', '-');
+                break;
+            }
+            item => {
+                let range_end = iter.error(RegexErrorKind::InvalidClassRangeEnd, end_position);
+                // `[a-[:digit:]]`のように、POSIXクラスは範囲の端にできない
+                if item == Item::SquareL && posix_class(&mut iter.clone())?.is_some() {
+                    return Err(range_end);
+                }
+                let end = item.to_char().ok_or(range_end)?;
+                if end < start {
+                    let kind = RegexErrorKind::InvalidClassRange(start, end);
+                    return Err(iter.error(kind, range_position));
+                }
+                class.push(start, end);
+            }
+        }
+    }
+
+    // `(?i)[^a]`が`A`も除くよう、反転する前に広げる
+    if iter.case_insensitive() {
+        class = class.case_fold();
+    }
+    if negated {
+        Ok(class.negate())
     } else {
-        let edge = NfaEdge::new_alphabet(next_token);
-        let mut node = NfaNode::new_non_terminal();
-        let child = Default::default();
-        node.add_child(edge, Rc::clone(&child));
-        Some((Rc::new(RefCell::new(node)), child))
+        Ok(class)
+    }
+}
+
+/// word = Alphabet | "(" expr ")" | flags | class | "{" Name "}"
+pub fn word(iter: &mut RegexTokenIter) -> Result<Option<RegexAst>, RegexError> {
+    let open_position = iter.offset();
+    match iter.peek() {
+        Some(Item::SquareL) => {
+            iter.next();
+            Ok(Some(RegexAst::Class(char_class(iter)?)))
+        }
+        Some(Item::BracketL) => {
+            iter.next();
+            if iter.peek() == Some(Item::ZeroOrOne) {
+                iter.next();
+                return flag_group(iter, open_position);
+            }
+            // 括弧の中の`(?i)`は括弧の外には効かない
+            let case_insensitive = iter.case_insensitive();
+            let inner = expr(iter)?;
+            iter.set_case_insensitive(case_insensitive);
+            close_group(iter, open_position)?;
+            Ok(Some(RegexAst::Group(Box::new(inner))))
+        }
+        Some(Item::Reference(idx)) => {
+            iter.next();
+            let name = iter.reference_name(idx).to_string();
+            if iter.is_expanding(&name) {
+                let kind = RegexErrorKind::RecursiveDefinition(name);
+                return Err(iter.error(kind, open_position));
+            }
+            match iter.definition(&name)? {
+                Some(mut inner) => {
+                    let ast = expr(&mut inner)?;
+                    finish(&mut inner)?;
+                    Ok(Some(RegexAst::Group(Box::new(ast))))
+                }
+                None => Err(iter.error(RegexErrorKind::UndefinedName(name), open_position)),
+            }
+        }
+        _ => Ok(alphabet(iter)),
+    }
+}
+
+/// 括弧の中のexprの後の")"を読む
+fn close_group(iter: &mut RegexTokenIter, open_position: usize) -> Result<(), RegexError> {
+    let position = iter.offset();
+    match iter.next() {
+        Some(Item::BracketR) => Ok(()),
+        Some(Item::Slash) => Err(iter.error(RegexErrorKind::MisplacedTrailingContext, position)),
+        _ => Err(iter.error(RegexErrorKind::UnclosedGroup, open_position)),
+    }
+}
+
+/// flags = "(?" "-"? "i" ( ")" | ":" expr ")" )
+/// "(?"は読み終わっている前提
+/// `(?i)`は囲んでいる括弧の終わりまで、`(?i:..)`はその括弧の中だけ効く
+/// `(?i)`自体は空文字列になる
+fn flag_group(
+    iter: &mut RegexTokenIter,
+    open_position: usize,
+) -> Result<Option<RegexAst>, RegexError> {
+    let unclosed = |iter: &RegexTokenIter| iter.error(RegexErrorKind::UnclosedGroup, open_position);
+    let enable = iter.peek() != Some(Item::Char('-'));
+    if !enable {
+        iter.next();
+    }
+    let flag_position = iter.offset();
+    match iter.next() {
+        Some(Item::Char('i')) => {}
+        Some(_) => return Err(iter.error(RegexErrorKind::InvalidFlag, flag_position)),
+        None => return Err(unclosed(iter)),
+    }
+
+    let position = iter.offset();
+    match iter.next() {
+        Some(Item::BracketR) => {
+            iter.set_case_insensitive(enable);
+            Ok(Some(RegexAst::empty()))
+        }
+        Some(Item::Char(':')) => {
+            let case_insensitive = iter.case_insensitive();
+            iter.set_case_insensitive(enable);
+            let inner = expr(iter)?;
+            iter.set_case_insensitive(case_insensitive);
+            close_group(iter, open_position)?;
+            Ok(Some(RegexAst::Group(Box::new(inner))))
+        }
+        Some(_) => Err(iter.error(RegexErrorKind::InvalidFlag, position)),
+        None => Err(unclosed(iter)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct RepConfig {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl RepConfig {
+    fn new(min: usize, max: Option<usize>) -> Self {
+        Self { min, max }
+    }
+}
+
+/// `{m,n}`の`m`と`n`の上限
+/// 繰り返しはNFAの状態をその回数だけ複製するので、大きな回数はメモリと時間を使い切る
+pub const MAX_REPETITION: usize = 1000;
+
+/// 連続した`Digit`を一つの数として読む
+/// `MAX_REPETITION`を超えればエラー
+fn parse_number(iter: &mut RegexTokenIter) -> Result<Option<usize>, RegexError> {
+    let position = iter.offset();
+    let mut number = None;
+    while let Some(Item::Digit(x)) = iter.peek() {
+        iter.next();
+        let current: usize = number.unwrap_or(0);
+        let next = current
+            .checked_mul(10)
+            .and_then(|n| n.checked_add(x))
+            .filter(|n| *n <= MAX_REPETITION)
+            .ok_or_else(|| iter.error(RegexErrorKind::RepetitionTooLarge, position))?;
+        number = Some(next);
+    }
+    Ok(number)
+}
+
+/// rep_op = "*" | "+" | "?" | "{" min ( "," max? )? "}"
+fn parse_rep(iter: &mut RegexTokenIter) -> Result<Option<RepConfig>, RegexError> {
+    let open_position = iter.offset();
+    let config = match iter.peek() {
+        // *
+        Some(Item::SomeTime) => RepConfig::new(0, None),
+        // +
+        Some(Item::OneOrMore) => RepConfig::new(1, None),
+        // ?
+        Some(Item::ZeroOrOne) => RepConfig::new(0, Some(1)),
+        // "{" min ","? max? "}"
+        Some(Item::CurryL) => {
+            iter.next();
+            let min = parse_number(iter)?.ok_or_else(|| {
+                let kind = match iter.peek() {
+                    Some(Item::Char(',')) => RegexErrorKind::MissingRepetitionMin,
+                    None => RegexErrorKind::UnclosedRepetition,
+                    _ => RegexErrorKind::InvalidRepetition,
+                };
+                iter.error(kind, iter.offset())
+            })?;
+            let max = match iter.next() {
+                // "{" min "}"
+                Some(Item::CurryR) => return Ok(Some(RepConfig::new(min, Some(min)))),
+                // "{" min "," max? "}"
+                Some(Item::Char(',')) => parse_number(iter)?,
+                None => return Err(iter.error(RegexErrorKind::UnclosedRepetition, open_position)),
+                _ => {
+                    let position = iter.prev_offset();
+                    return Err(iter.error(RegexErrorKind::InvalidRepetition, position));
+                }
+            };
+            match iter.next() {
+                Some(Item::CurryR) => {}
+                None => return Err(iter.error(RegexErrorKind::UnclosedRepetition, open_position)),
+                _ => {
+                    let position = iter.prev_offset();
+                    return Err(iter.error(RegexErrorKind::InvalidRepetition, position));
+                }
+            }
+            // { 5, 4 } はエラー
+            if let Some(max) = max.filter(|max| *max < min) {
+                let kind = RegexErrorKind::InvalidRepetitionRange(min, max);
+                return Err(iter.error(kind, open_position));
+            }
+            return Ok(Some(RepConfig::new(min, max)));
+        }
+        _ => return Ok(None),
+    };
+    iter.next();
+    Ok(Some(config))
+}
+
+/// rep = word rep_op*
+/// 空文字列の繰り返しは空文字列のままにする
+pub fn rep(iter: &mut RegexTokenIter) -> Result<Option<RegexAst>, RegexError> {
+    let mut ast = match word(iter)? {
+        Some(ast) => ast,
+        None => return Ok(None),
+    };
+    while let Some(config) = parse_rep(iter)? {
+        if !ast.is_empty() {
+            ast = RegexAst::Repeat {
+                inner: Box::new(ast),
+                min: config.min,
+                max: config.max,
+            };
+        }
+    }
+    Ok(Some(ast))
+}
+
+/// concat = rep*
+/// 一つもrepが無い場合は空文字列を受理する
+/// `(?i)`が残す空文字列は連結に含めない
+pub fn concat(iter: &mut RegexTokenIter) -> Result<RegexAst, RegexError> {
+    let mut items = Vec::new();
+    while let Some(ast) = rep(iter)? {
+        if !ast.is_empty() {
+            items.push(ast);
+        }
+    }
+    if items.len() == 1 {
+        Ok(items.pop().unwrap())
+    } else {
+        Ok(RegexAst::Concat(items))
+    }
+}
+
+/// expr = concat ( "|" concat )*
+pub fn expr(iter: &mut RegexTokenIter) -> Result<RegexAst, RegexError> {
+    let first = concat(iter)?;
+    if iter.peek() != Some(Item::Or) {
+        return Ok(first);
+    }
+    let mut branches = vec![first];
+    while iter.peek() == Some(Item::Or) {
+        iter.next();
+        branches.push(concat(iter)?);
+    }
+    Ok(RegexAst::Alternate(branches))
+}
+
+/// exprの後にトークンが残っていないことを確かめる
+/// exprが読み残すのは、どこにも当てはまらないトークンだけ
+fn finish(iter: &mut RegexTokenIter) -> Result<(), RegexError> {
+    let position = iter.offset();
+    let Some(item) = iter.next() else {
+        return Ok(());
+    };
+    let kind = match item {
+        Item::BracketR => RegexErrorKind::UnopenedGroup,
+        Item::SomeTime | Item::OneOrMore | Item::ZeroOrOne | Item::CurryL => {
+            RegexErrorKind::NothingToRepeat
+        }
+        Item::CurryR => RegexErrorKind::UnexpectedChar('}'),
+        Item::Slash => RegexErrorKind::MisplacedTrailingContext,
+        Item::Bol | Item::Eol => RegexErrorKind::MisplacedAnchor,
+        _ => RegexErrorKind::UnexpectedChar(']'),
+    };
+    Err(iter.error(kind, position))
+}
+
+/// `^`と`$`を`body`の両端に付ける
+/// `body`が選択なら、`^(a|b)`のように括弧で囲んでから付ける
+fn anchored(bol: bool, body: RegexAst, eol: bool) -> RegexAst {
+    if !bol && !eol {
+        return body;
+    }
+    let mut items = Vec::new();
+    if bol {
+        items.push(RegexAst::Anchor(Anchor::StartLine));
+    }
+    match body {
+        RegexAst::Concat(body) => items.extend(body),
+        RegexAst::Alternate(_) => items.push(RegexAst::Group(Box::new(body))),
+        body => items.push(body),
+    }
+    if eol {
+        items.push(RegexAst::Anchor(Anchor::EndLine));
+    }
+    RegexAst::Concat(items)
+}
+
+/// rule = "^"? expr ( "/" expr | "$" )?
+/// `r/s`は`Trailing`に、`^`と`$`は両端の`Anchor`にする
+fn rule(iter: &mut RegexTokenIter) -> Result<RegexAst, RegexError> {
+    let bol = iter.peek() == Some(Item::Bol);
+    if bol {
+        iter.next();
+    }
+    let head = expr(iter)?;
+    match iter.peek() {
+        Some(Item::Slash) => {}
+        Some(Item::Eol) => {
+            iter.next();
+            return Ok(anchored(bol, head, true));
+        }
+        _ => return Ok(anchored(bol, head, false)),
+    }
+    let position = iter.offset();
+    iter.next();
+    let tail = expr(iter)?;
+    if head.fixed_len().is_none() && tail.fixed_len().is_none() {
+        return Err(iter.error(RegexErrorKind::VariableTrailingContext, position));
+    }
+    if iter.peek() == Some(Item::Eol) {
+        return Err(iter.error(RegexErrorKind::MisplacedAnchor, iter.offset()));
+    }
+    let trailing = RegexAst::Trailing(Box::new(head), Box::new(tail));
+    Ok(anchored(bol, trailing, false))
+}
+
+/// 正規表現をパースして構文木にする
+pub fn parse_ast(regex: &Regex) -> Result<RegexAst, RegexError> {
+    let mut iter = regex.tokens_iter();
+    let ast = rule(&mut iter)?;
+    finish(&mut iter)?;
+    Ok(ast)
+}
+
+/// 正規表現をパースして`nfa`に追加し、`starts`の番号の開始状態から繋ぐ
+/// 構文木は簡約してからNFAにする
+/// `^`のルールは行頭の開始状態から繋ぐ
+/// 受理状態には`terminal`を設定する
+pub fn parse_rule<T: Clone>(
+    nfa: &mut Nfa<T>,
+    regex: &Regex,
+    terminal: T,
+    starts: &[usize],
+) -> Result<NfaPair, RegexError> {
+    let ast = simplify(parse_ast(regex)?);
+    // パーサーが作って簡約した構文木は、コンパイルできる形になっている
+    compile_into(nfa, &ast, terminal, starts)
+        .map_err(|kind| RegexError::new(kind, regex.as_str(), 0))
+}
+
+/// 正規表現をパースして`nfa`に追加し、開始状態から繋ぐ
+/// 受理状態には`terminal`を設定する
+pub fn parse_into<T: Clone>(
+    nfa: &mut Nfa<T>,
+    regex: &Regex,
+    terminal: T,
+) -> Result<NfaPair, RegexError> {
+    parse_rule(nfa, regex, terminal, &[0])
+}
+
+/// 正規表現一つ分のNFAを作る
+pub fn parse<T: Clone>(regex: &Regex, terminal: T) -> Result<Nfa<T>, RegexError> {
+    let mut nfa = Nfa::new();
+    parse_into(&mut nfa, regex, terminal)?;
+    Ok(nfa)
+}
+
+#[cfg(test)]
+mod rep_config_test {
+    use super::*;
+
+    macro_rules! rep_config {
+        ($fn_name:ident, $regex_string:expr, $ans:expr) => {
+            #[test]
+            fn $fn_name() {
+                let regex = Regex::new($regex_string).unwrap();
+                let mut regex_iter = regex.tokens_iter();
+                let rep_config = parse_rep(&mut regex_iter).unwrap().unwrap();
+                assert_eq!($ans, rep_config);
+                assert_eq!(None, regex_iter.next());
+            }
+        };
+    }
+
+    rep_config!(
+        rep_config_struct_gen_ast,
+        "*".to_string(),
+        RepConfig::new(0, None)
+    );
+    rep_config!(
+        rep_config_struct_gen_qus,
+        "?".to_string(),
+        RepConfig::new(0, Some(1))
+    );
+    rep_config!(
+        rep_config_struct_gen_plus,
+        "+".to_string(),
+        RepConfig::new(1, None)
+    );
+    rep_config!(
+        rep_config_struct_gen_num,
+        "{2}".to_string(),
+        RepConfig::new(2, Some(2))
+    );
+    rep_config!(
+        rep_config_struct_gen_num_num,
+        "{2,3}".to_string(),
+        RepConfig::new(2, Some(3))
+    );
+    rep_config!(
+        rep_config_struct_gen_num_open,
+        "{2,}".to_string(),
+        RepConfig::new(2, None)
+    );
+    rep_config!(
+        rep_config_struct_gen_multi_digit,
+        "{10,255}".to_string(),
+        RepConfig::new(10, Some(255))
+    );
+    rep_config!(
+        rep_config_struct_gen_zero,
+        "{0}".to_string(),
+        RepConfig::new(0, Some(0))
+    );
+
+    #[test]
+    fn not_rep() {
+        let regex = Regex::new("a".to_string()).unwrap();
+        assert_eq!(None, parse_rep(&mut regex.tokens_iter()).unwrap());
+    }
+
+    #[test]
+    fn max_smaller_than_min() {
+        let regex = Regex::new("{5,4}".to_string()).unwrap();
+        let error = parse_rep(&mut regex.tokens_iter()).unwrap_err();
+        assert_eq!(error.kind(), &RegexErrorKind::InvalidRepetitionRange(5, 4));
     }
 }
@@ -0,0 +1,311 @@
+//! 一致する文字列を変えずに構文木を小さくする
+//! NFAを作る前にかけて、キーワードの多い仕様でNFAの状態数を減らす
+//!
+//! - `(..)`を外し、入れ子の連結と選択を平らにする 括弧が要る所には`Group`を置き直す
+//! - 同じ選択肢を一つにし、一文字の選択肢は一つの文字クラスにまとめる
+//! - 先頭の文字が同じ選択肢をくくり出す (`if|in|int`は`i(f|nt?)`)
+//! - `(x*)*`のような`*`, `+`, `?`の入れ子と、`x{1}`, `x{0}`を畳む
+//!
+//! 一つのルールの中では選択肢の順番で一致が変わらないので、選択肢は並べ替えてよい
+//! ルール全体の`Concat`の両端の`Anchor`と、`Trailing`はそのままの位置に残る
+use crate::char_class::CharClass;
+use crate::regex_ast::RegexAst;
+
+/// 一文字だけのクラスは`Literal`にする
+fn class_or_literal(class: CharClass) -> RegexAst {
+    match class.ranges() {
+        [(start, end)] if start == end => RegexAst::Literal(*start),
+        _ => RegexAst::Class(class),
+    }
+}
+
+/// 簡約済みの要素の連結 入れ子の連結を平らにして、空文字列を除く
+/// `Anchor`はパーサーと同じく`Concat`の中に残す
+fn concat(items: Vec<RegexAst>) -> RegexAst {
+    let mut flat = Vec::new();
+    for item in items {
+        match item {
+            RegexAst::Concat(inner) => flat.extend(inner),
+            item => flat.push(item),
+        }
+    }
+    if flat.len() == 1 && !matches!(flat[0], RegexAst::Anchor(_)) {
+        flat.pop().unwrap()
+    } else {
+        RegexAst::Concat(flat)
+    }
+}
+
+/// 選択肢の先頭の文字
+fn first_literal(branch: &RegexAst) -> Option<char> {
+    match branch {
+        RegexAst::Literal(c) => Some(*c),
+        RegexAst::Concat(items) => match items.first() {
+            Some(RegexAst::Literal(c)) => Some(*c),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// 選択肢から先頭の文字を除いた残り
+fn rest_after_first(branch: RegexAst) -> RegexAst {
+    match branch {
+        RegexAst::Concat(mut items) => {
+            items.remove(0);
+            concat(items)
+        }
+        _ => RegexAst::empty(),
+    }
+}
+
+/// 先頭の文字が同じ選択肢をまとめて、`ab|ac`を`a(b|c)`にする
+/// まとめた選択肢は、その文字で始まる最初の選択肢の位置に置く
+fn factor(branches: Vec<RegexAst>) -> Vec<RegexAst> {
+    let mut groups: Vec<(Option<char>, Vec<RegexAst>)> = Vec::new();
+    for branch in branches {
+        let first = first_literal(&branch);
+        match groups
+            .iter_mut()
+            .find(|(c, _)| first.is_some() && *c == first)
+        {
+            Some((_, members)) => members.push(branch),
+            None => groups.push((first, vec![branch])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(first, mut members)| match first {
+            Some(c) if members.len() > 1 => {
+                let rests = members.into_iter().map(rest_after_first).collect();
+                concat(vec![RegexAst::Literal(c), alternate(rests)])
+            }
+            _ => members.pop().unwrap(),
+        })
+        .collect()
+}
+
+/// 一文字の選択肢を、最初の一文字の選択肢の位置で一つの文字クラスにまとめる
+fn merge_chars(branches: Vec<RegexAst>) -> Vec<RegexAst> {
+    let mut class = CharClass::new();
+    let mut position = None;
+    let mut rest = Vec::new();
+    for branch in branches {
+        match branch {
+            RegexAst::Literal(c) => class.push(c, c),
+            RegexAst::Class(other) => class.union(&other),
+            branch => {
+                rest.push(branch);
+                continue;
+            }
+        }
+        position.get_or_insert(rest.len());
+    }
+    if let Some(position) = position {
+        rest.insert(position, class_or_literal(class));
+    }
+    rest
+}
+
+/// 簡約済みの選択肢の選択
+/// 空文字列の選択肢は、残りの選択肢を`?`で囲んで表す
+fn alternate(branches: Vec<RegexAst>) -> RegexAst {
+    let mut flat = Vec::new();
+    for branch in branches {
+        let inner = match branch {
+            RegexAst::Alternate(inner) => inner,
+            branch => vec![branch],
+        };
+        for branch in inner {
+            if !flat.contains(&branch) {
+                flat.push(branch);
+            }
+        }
+    }
+    let optional = flat.iter().any(RegexAst::is_empty);
+    flat.retain(|branch| !branch.is_empty());
+    let mut branches = merge_chars(factor(flat));
+    let ast = if branches.len() == 1 {
+        branches.pop().unwrap()
+    } else if branches.is_empty() {
+        RegexAst::empty()
+    } else {
+        RegexAst::Alternate(branches)
+    };
+    if optional {
+        repeat(ast, 0, Some(1))
+    } else {
+        ast
+    }
+}
+
+/// `*`, `+`, `?`のどれか
+fn is_star_like(min: usize, max: Option<usize>) -> bool {
+    min <= 1 && matches!(max, None | Some(1))
+}
+
+/// 簡約済みの`inner`の`min`回以上`max`回以下の繰り返し
+fn repeat(inner: RegexAst, min: usize, max: Option<usize>) -> RegexAst {
+    match inner {
+        inner if min == 1 && max == Some(1) => inner,
+        _ if max == Some(0) => RegexAst::empty(),
+        inner if inner.is_empty() => inner,
+        // `(x+)?`は`x*`、`(x?)?`は`x?`のように、`*`, `+`, `?`の入れ子は一つにできる
+        RegexAst::Repeat {
+            inner,
+            min: inner_min,
+            max: inner_max,
+        } if is_star_like(inner_min, inner_max) && is_star_like(min, max) => {
+            let max = if inner_max.is_some() && max.is_some() {
+                Some(1)
+            } else {
+                None
+            };
+            repeat(*inner, inner_min * min, max)
+        }
+        inner => RegexAst::Repeat {
+            inner: Box::new(inner),
+            min,
+            max,
+        },
+    }
+}
+
+/// `Group`を外して簡約する
+fn simplify_node(ast: RegexAst) -> RegexAst {
+    match ast {
+        RegexAst::Group(inner) => simplify_node(*inner),
+        RegexAst::Class(class) => class_or_literal(class),
+        RegexAst::Concat(items) => concat(items.into_iter().map(simplify_node).collect()),
+        RegexAst::Alternate(branches) => {
+            alternate(branches.into_iter().map(simplify_node).collect())
+        }
+        RegexAst::Repeat { inner, min, max } => repeat(simplify_node(*inner), min, max),
+        RegexAst::Trailing(head, tail) => RegexAst::Trailing(
+            Box::new(simplify_node(*head)),
+            Box::new(simplify_node(*tail)),
+        ),
+        ast @ (RegexAst::Literal(_) | RegexAst::Anchor(_)) => ast,
+    }
+}
+
+/// 書き戻すと`(..)`が付く所を`Group`で囲み、パーサーが作るのと同じ形にする
+fn regroup(ast: RegexAst) -> RegexAst {
+    let group = |ast| RegexAst::Group(Box::new(ast));
+    match ast {
+        RegexAst::Concat(items) => RegexAst::Concat(
+            items
+                .into_iter()
+                .map(|item| match regroup(item) {
+                    item @ RegexAst::Alternate(_) => group(item),
+                    item => item,
+                })
+                .collect(),
+        ),
+        RegexAst::Alternate(branches) => {
+            RegexAst::Alternate(branches.into_iter().map(regroup).collect())
+        }
+        RegexAst::Repeat { inner, min, max } => {
+            let inner = match regroup(*inner) {
+                inner @ (RegexAst::Literal(_) | RegexAst::Class(_) | RegexAst::Repeat { .. }) => {
+                    inner
+                }
+                inner => group(inner),
+            };
+            RegexAst::Repeat {
+                inner: Box::new(inner),
+                min,
+                max,
+            }
+        }
+        RegexAst::Trailing(head, tail) => {
+            RegexAst::Trailing(Box::new(regroup(*head)), Box::new(regroup(*tail)))
+        }
+        ast => ast,
+    }
+}
+
+/// 構文木を簡約する
+/// 簡約した構文木は、書き戻してパースし直すと同じ構文木になる
+pub fn simplify(ast: RegexAst) -> RegexAst {
+    regroup(simplify_node(ast))
+}
+
+#[cfg(test)]
+mod regex_simplifier_test {
+    use super::*;
+    use crate::regex_compiler::compile;
+    use crate::regex_compiler::regex_compiler_test::positions;
+    use crate::regex_parser::parse_ast;
+    use crate::regex_tokenizer::Regex;
+
+    fn parse(regex: &str) -> RegexAst {
+        parse_ast(&Regex::new(regex.to_string()).unwrap()).unwrap()
+    }
+
+    /// 簡約した構文木を書き戻すと`$ans`になり、パースし直すと同じ構文木になる
+    /// 簡約の前と同じ位置で受理する
+    macro_rules! simplify_test {
+        ($fn_name:ident, $regex:expr, $ans:expr, $($query:expr),*) => {
+            #[test]
+            fn $fn_name() {
+                let ast = parse($regex);
+                let simplified = simplify(ast.clone());
+                assert_eq!(simplified.to_string(), $ans);
+                assert_eq!(parse($ans), simplified);
+                $(
+                    assert_eq!(
+                        positions(&simplified, $query),
+                        positions(&ast, $query),
+                        "query: {:?}",
+                        $query
+                    );
+                )*
+            }
+        };
+    }
+
+    simplify_test!(group, "((a)(bc))", "abc", "abc", "ab");
+    simplify_test!(same_branches, "(a|a)", "a", "a");
+    simplify_test!(nested_alternate, "a|(b|(c|d))", "[a-d]", "c", "e");
+    simplify_test!(merge_classes, "[a-c]|[b-d]", "[a-d]", "d", "e");
+    simplify_test!(single_char_class, "[a]b", "ab", "ab");
+    simplify_test!(once, "a{1}b{0}c", "ac", "ac", "abc");
+    simplify_test!(nested_star, "(x*)*", "x*", "", "xx");
+    simplify_test!(plus_in_question, "(x+)?y", "x*y", "y", "xxy");
+    simplify_test!(question_in_question, "(x?)?", "x?", "", "x", "xx");
+    simplify_test!(
+        counted_not_collapsed,
+        "(x{2,})*",
+        "x{2,}*",
+        "x",
+        "xx",
+        "xxx"
+    );
+    simplify_test!(keywords, "if|in|int", "i(f|nt?)", "if", "in", "int", "i");
+    simplify_test!(
+        common_prefix,
+        "for|foreach|fn|x",
+        "f(or(each)?|n)|x",
+        "for",
+        "foreach",
+        "fn",
+        "fore"
+    );
+    simplify_test!(empty_branch, "a|", "a?", "", "a");
+    simplify_test!(only_empty, "(|)b", "b", "b");
+    simplify_test!(anchors, "^(a|b)$", "^[ab]$", "a", "a\n", "c");
+    simplify_test!(only_anchor, "^", "^", "", "a");
+    simplify_test!(quoted, r#""a.b""#, r"a\.b", "a.b", "axb");
+    simplify_test!(group_repeat, "(ab)*(c)+", "(ab)*c+", "ababc", "cc");
+    simplify_test!(trailing, "(ab|ac)/(d)", "a[bc]/d", "abd", "acd", "ab");
+
+    #[test]
+    fn smaller_nfa() {
+        let ast = parse("if|in|int|for|foreach|fn|while|when");
+        let simplified = simplify(ast.clone());
+        let before = compile(&ast, ()).unwrap().len();
+        let after = compile(&simplified, ()).unwrap().len();
+        assert!(after < before, "{} -> {}", before, after);
+    }
+}
@@ -1,5 +1,17 @@
 //! 正規表現のトークナイザー
 //! 特殊記号、数字、などを分離してトークンにする
+//! `{DIGIT}`のように`{`と`}`で名前を囲むと、`[definitions]`の定義の参照になる
+//! `"a+b"`のように`"`で囲んだ部分は、`\"`と`\\`などのエスケープ以外全て文字として扱う
+//! `"ab"+`が`ab`の繰り返しになるよう、囲んだ部分は`(`と`)`のトークンで挟む
+//! 文字クラスの中の`"`はただの文字
+//! `r/s`の`/`は、クラスと`"`の外にある時だけ`Item::Slash`にする
+//! 正規表現の先頭の`^`は`Item::Bol`、末尾の`$`は`Item::Eol`にする それ以外はただの文字
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::char_class::CharClass;
+use crate::regex_error::{RegexError, RegexErrorKind};
+use crate::unicode::PropertyId;
 
 /// トークンの種類を表す
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -9,10 +21,21 @@ pub enum Item {
     /// \D
     LargeD,
 
-    // /// \s
-    // SmallS,
-    // /// \S
-    // LargeS,
+    /// \s
+    SmallS,
+    /// \S
+    LargeS,
+
+    /// \w
+    SmallW,
+    /// \W
+    LargeW,
+
+    /// \p{..}
+    SmallP(PropertyId),
+    /// \P{..}
+    LargeP(PropertyId),
+
     /// 0-9
     Digit(usize),
     /// a-z, A-Z
@@ -75,6 +98,115 @@ pub enum Item {
 
     /// \
     BackSlash,
+
+    /// \-
+    Hyphen,
+
+    /// \^
+    Caret,
+
+    /// {NAME} 名前は`RegexTokenIter::reference_name`で引く
+    Reference(usize),
+
+    /// r/s の /
+    Slash,
+
+    /// 先頭の ^
+    Bol,
+    /// 末尾の $
+    Eol,
+}
+
+impl Item {
+    /// 一文字として扱うトークンを文字に戻す
+    /// `[.*+]`のように、文字クラスの中では特殊記号も文字として扱う
+    pub fn to_char(self) -> Option<char> {
+        let char_ = match self {
+            Item::Char(c) => c,
+            Item::Digit(digit) => char::from_digit(digit as u32, 10)?,
+            Item::Plus | Item::OneOrMore => '+',
+            Item::Dot | Item::Any => '.',
+            Item::Ast | Item::SomeTime => '*',
+            Item::Pipe | Item::Or => '|',
+            Item::Question | Item::ZeroOrOne => '?',
+            Item::BracketLInner | Item::BracketL => '(',
+            Item::BracketRInner | Item::BracketR => ')',
+            Item::CurryLInner | Item::CurryL => '{',
+            Item::CurryRInner | Item::CurryR => '}',
+            Item::SquareLInner | Item::SquareL => '[',
+            Item::SquareRInner => ']',
+            Item::BackSlash => '\\',
+            Item::Hyphen => '-',
+            Item::Caret => '^',
+            Item::Slash => '/',
+            Item::Bol => '^',
+            Item::Eol => '$',
+            Item::SmallD
+            | Item::LargeD
+            | Item::SmallS
+            | Item::LargeS
+            | Item::SmallW
+            | Item::LargeW
+            | Item::SmallP(_)
+            | Item::LargeP(_)
+            | Item::Reference(_)
+            | Item::SquareR => return None,
+        };
+        Some(char_)
+    }
+
+    /// `\d`のように、それだけで文字の集合を表すトークンのクラス
+    pub fn shorthand_class(self) -> Option<CharClass> {
+        let digit = CharClass::from_ranges([('0', '9')]);
+        let space = CharClass::from_ranges([('\t', '\r'), (' ', ' ')]);
+        let word = CharClass::from_ranges([('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]);
+        match self {
+            Item::SmallD => Some(digit),
+            Item::LargeD => Some(digit.negate()),
+            Item::SmallS => Some(space),
+            Item::LargeS => Some(space.negate()),
+            Item::SmallW => Some(word),
+            Item::LargeW => Some(word.negate()),
+            Item::SmallP(id) => Some(id.class()),
+            Item::LargeP(id) => Some(id.class().negate()),
+            _ => None,
+        }
+    }
+
+    /// トークンを正規表現に書き戻す
+    /// `Dot`は`\.`、`Any`は`.`のように、エスケープした方はエスケープして書く
+    /// `Char`はそのまま書くので、`"`や`/`のエスケープは呼び出し側で決める
+    /// 名前を持たない`Reference`は書けない
+    pub fn to_pattern(self) -> Option<String> {
+        let pattern = match self {
+            Item::SmallD => r"\d".to_string(),
+            Item::LargeD => r"\D".to_string(),
+            Item::SmallS => r"\s".to_string(),
+            Item::LargeS => r"\S".to_string(),
+            Item::SmallW => r"\w".to_string(),
+            Item::LargeW => r"\W".to_string(),
+            Item::SmallP(id) => format!(r"\p{{{}}}", id.name()),
+            Item::LargeP(id) => format!(r"\P{{{}}}", id.name()),
+            Item::Plus
+            | Item::Dot
+            | Item::Ast
+            | Item::Pipe
+            | Item::Question
+            | Item::BracketLInner
+            | Item::BracketRInner
+            | Item::CurryLInner
+            | Item::CurryRInner
+            | Item::SquareLInner
+            | Item::SquareRInner
+            | Item::BackSlash
+            | Item::Hyphen
+            | Item::Caret => format!("\\{}", self.to_char()?),
+            Item::SquareR => "]".to_string(),
+            Item::Reference(_) => return None,
+            _ => self.to_char()?.to_string(),
+        };
+        Some(pattern)
+    }
 }
 
 // impl Content for Item {}
@@ -110,11 +242,27 @@ fn is_digit(char: &char) -> bool {
     ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'].contains(char)
 }
 
+/// \sに一致する文字 (空白, \t, \n, \v, \f, \r)
+fn is_space(char: &char) -> bool {
+    [' ', '\t', '\n', '\u{B}', '\u{C}', '\r'].contains(char)
+}
+
+/// \wに一致する文字 (英数字と_)
+fn is_word(char: &char) -> bool {
+    char.is_ascii_alphanumeric() || *char == '_'
+}
+
 impl PartialEq<char> for Item {
     fn eq(&self, other: &char) -> bool {
         match *self {
             Item::SmallD => is_digit(other),
             Item::LargeD => !is_digit(other),
+            Item::SmallS => is_space(other),
+            Item::LargeS => !is_space(other),
+            Item::SmallW => is_word(other),
+            Item::LargeW => !is_word(other),
+            Item::SmallP(id) => id.contains(*other),
+            Item::LargeP(id) => !id.contains(*other),
             Item::Digit(digit) => char::from_digit(digit as u32, 10).unwrap() == *other,
             Item::Char(char_) => char_ == *other,
             Item::Plus => *other == '+',
@@ -122,15 +270,18 @@ impl PartialEq<char> for Item {
             Item::Ast => *other == '*',
             Item::Pipe => *other == '|',
             Item::Question => *other == '?',
-            Item::BracketRInner => *other == '(',
-            Item::BracketLInner => *other == ')',
-            Item::CurryRInner => *other == '{',
-            Item::CurryLInner => *other == '}',
-            Item::SquareRInner => *other == '[',
-            Item::SquareLInner => *other == ']',
+            Item::BracketLInner => *other == '(',
+            Item::BracketRInner => *other == ')',
+            Item::CurryLInner => *other == '{',
+            Item::CurryRInner => *other == '}',
+            Item::SquareLInner => *other == '[',
+            Item::SquareRInner => *other == ']',
             Item::BackSlash => *other == '\\',
+            Item::Hyphen => *other == '-',
+            Item::Caret => *other == '^',
             Item::Any => true,
-            _ => unreachable!(),
+            // 構造を表すトークンはどの文字とも一致しない
+            _ => false,
         }
     }
 }
@@ -147,6 +298,8 @@ impl From<char> for Item {
             Item::BackSlash
         } else if c == '*' {
             Item::Ast
+        } else if c == '+' {
+            Item::Plus
         } else if c == '.' {
             Item::Dot
         } else if c == '|' {
@@ -165,6 +318,10 @@ impl From<char> for Item {
             Item::SquareLInner
         } else if c == ']' {
             Item::SquareRInner
+        } else if c == '-' {
+            Item::Hyphen
+        } else if c == '^' {
+            Item::Caret
         } else {
             if let Some(item) = try_digit(c) {
                 item
@@ -175,42 +332,109 @@ impl From<char> for Item {
     }
 }
 
+/// `{NAME}`で参照できる名前付きの正規表現
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Definitions {
+    inner: HashMap<String, String>,
+}
+
+impl Definitions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: String, regex: String) {
+        self.inner.insert(name, regex);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.inner.get(name).map(String::as_str)
+    }
+}
+
 pub struct Regex {
     string: String,
+    tokens: Vec<(Item, usize)>,
+    names: Vec<String>,
+    case_insensitive: bool,
+    definitions: Rc<Definitions>,
 }
 
 impl Regex {
-    pub fn new(string: String) -> Self {
-        Regex { string }
+    /// 正規表現をトークナイズする
+    /// 不正なエスケープなどがあれば、その位置を持った`RegexError`を返す
+    pub fn new(string: String) -> Result<Self, RegexError> {
+        let mut scanner = Scanner::new(&string);
+        let tokens = scanner.by_ref().collect::<Result<Vec<_>, _>>()?;
+        let names = scanner.names;
+        Ok(Regex {
+            string,
+            tokens,
+            names,
+            case_insensitive: false,
+            definitions: Rc::default(),
+        })
+    }
+
+    /// `{NAME}`を展開する時に引く定義
+    pub fn set_definitions(&mut self, definitions: Rc<Definitions>) {
+        self.definitions = definitions;
+    }
+
+    /// 正規表現全体を`(?i)`で始めたのと同じにする
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.string
     }
 
     pub fn tokens_iter(&self) -> RegexTokenIter {
         RegexTokenIter {
-            item: self.string.chars().collect(),
+            pattern: self.string.clone(),
+            item: self.tokens.clone(),
             idx: 0,
+            names: self.names.clone(),
+            case_insensitive: self.case_insensitive,
+            definitions: Rc::clone(&self.definitions),
+            expanding: Vec::new(),
         }
     }
 }
 
-fn parse_backslash(char_: Option<char>) -> Item {
+fn parse_backslash(char_: Option<char>) -> Result<Item, RegexErrorKind> {
     match char_ {
-        Some('d') => Item::SmallD,
-        Some('D') => Item::LargeD,
-        // Some('s') => Item::SmallS,
-        // Some('S') => Item::LargeS,
-        Some('.') => Item::Dot,
-        Some('*') => Item::Ast,
-        Some('|') => Item::Pipe,
-        Some('?') => Item::Question,
-        Some('(') => Item::BracketLInner,
-        Some(')') => Item::BracketRInner,
-        Some('{') => Item::CurryLInner,
-        Some('}') => Item::CurryRInner,
-        Some('[') => Item::SquareLInner,
-        Some(']') => Item::SquareRInner,
-        Some('\\') => Item::BackSlash,
-        Some(x) => panic!("{}", format!("{} does not follow a backslash", x)),
-        None => panic!("backslash cannot end a regular expression."),
+        Some('d') => Ok(Item::SmallD),
+        Some('D') => Ok(Item::LargeD),
+        Some('s') => Ok(Item::SmallS),
+        Some('S') => Ok(Item::LargeS),
+        Some('w') => Ok(Item::SmallW),
+        Some('W') => Ok(Item::LargeW),
+        Some('t') => Ok(Item::Char('\t')),
+        Some('n') => Ok(Item::Char('\n')),
+        Some('r') => Ok(Item::Char('\r')),
+        Some('f') => Ok(Item::Char('\u{C}')),
+        Some('v') => Ok(Item::Char('\u{B}')),
+        Some('.') => Ok(Item::Dot),
+        Some('*') => Ok(Item::Ast),
+        Some('+') => Ok(Item::Plus),
+        Some('|') => Ok(Item::Pipe),
+        Some('?') => Ok(Item::Question),
+        Some('(') => Ok(Item::BracketLInner),
+        Some(')') => Ok(Item::BracketRInner),
+        Some('{') => Ok(Item::CurryLInner),
+        Some('}') => Ok(Item::CurryRInner),
+        Some('[') => Ok(Item::SquareLInner),
+        Some(']') => Ok(Item::SquareRInner),
+        Some('\\') => Ok(Item::BackSlash),
+        Some('-') => Ok(Item::Hyphen),
+        Some('^') => Ok(Item::Caret),
+        Some('"') => Ok(Item::Char('"')),
+        Some('/') => Ok(Item::Char('/')),
+        Some('$') => Ok(Item::Char('$')),
+        Some(x) => Err(RegexErrorKind::InvalidEscape(x)),
+        None => Err(RegexErrorKind::TrailingBackslash),
     }
 }
 
@@ -235,13 +459,92 @@ fn try_special_char(char: char) -> Option<Item> {
     }
 }
 
-#[derive(Debug)]
-pub struct RegexTokenIter {
+/// 定義の名前に使える文字列か
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let head = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    head && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// 文字列を一文字ずつ読み、(トークン, 文字単位の位置) を返す
+struct Scanner<'a> {
+    pattern: &'a str,
     item: Vec<char>,
     idx: usize,
+    /// `{NAME}`で参照した名前 `Item::Reference`はこの添字
+    names: Vec<String>,
+    /// `"`の中を読んでいる時は、開いた`"`の位置
+    quote: Option<usize>,
+    /// 文字クラスの`[`の深さ `[[:alpha:]]`の中は2
+    class_depth: usize,
+    /// 外側の文字クラスの最初の文字の位置 `[^`なら`^`の次
+    class_first: usize,
 }
 
-impl RegexTokenIter {
+impl<'a> Scanner<'a> {
+    fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            item: pattern.chars().collect(),
+            idx: 0,
+            names: Vec::new(),
+            quote: None,
+            class_depth: 0,
+            class_first: 0,
+        }
+    }
+
+    /// `"`の中の一文字を読む 閉じる`"`は`)`にする
+    fn quoted(&mut self) -> Result<Item, RegexErrorKind> {
+        match self.next_char() {
+            Some('"') => {
+                self.quote = None;
+                Ok(Item::BracketR)
+            }
+            Some('\\') => match self.peek_char() {
+                Some(c @ ('"' | '\\')) => {
+                    self.idx += 1;
+                    Ok(Item::Char(c))
+                }
+                // `\n`や`\x41`は一文字になるが、`\d`のような集合は使えない
+                Some(c) => self.escape().and_then(|item| {
+                    item.to_char()
+                        .map(Item::Char)
+                        .ok_or(RegexErrorKind::InvalidEscape(c))
+                }),
+                None => Err(RegexErrorKind::TrailingBackslash),
+            },
+            Some(c) => Ok(Item::Char(c)),
+            None => {
+                self.quote = None;
+                Err(RegexErrorKind::UnclosedQuote)
+            }
+        }
+    }
+
+    /// `{`の後ろが`NAME}`なら読んで参照にする
+    /// `{2,3}`のような回数指定なら何も読まない
+    fn reference(&mut self) -> Option<Item> {
+        let len = self.item[self.idx..].iter().position(|c| *c == '}')?;
+        let name = self.item[self.idx..self.idx + len]
+            .iter()
+            .collect::<String>();
+        if !is_name(&name) {
+            return None;
+        }
+        self.idx += len + 1;
+        let idx = match self.names.iter().position(|x| *x == name) {
+            Some(idx) => idx,
+            None => {
+                self.names.push(name);
+                self.names.len() - 1
+            }
+        };
+        Some(Item::Reference(idx))
+    }
+
     fn next_char(&mut self) -> Option<char> {
         if self.idx == self.item.len() {
             return None;
@@ -251,168 +554,241 @@ impl RegexTokenIter {
         res
     }
 
-    fn back(&mut self) {
-        self.idx -= 1;
+    fn peek_char(&self) -> Option<char> {
+        self.item.get(self.idx).copied()
+    }
+
+    /// 読む位置が`:alpha:]`や`:^alpha:]`のように、POSIXクラスの`[`の続きになっているか
+    /// 閉じる`:]`の無い`[:`はクラスの中の文字なので、深さを変えない
+    fn posix_class_ahead(&self) -> bool {
+        let Some(rest) = self.item[self.idx..].strip_prefix(&[':']) else {
+            return false;
+        };
+        let rest = rest.strip_prefix(&['^']).unwrap_or(rest);
+        let len = rest.iter().take_while(|c| c.is_ascii_alphabetic()).count();
+        rest[len..].starts_with(&[':', ']'])
+    }
+
+    /// `radix`進数の数字を最大`max`桁まで読んで値を返す
+    fn digits(&mut self, radix: u32, max: usize) -> Option<u32> {
+        let mut value = None;
+        for _ in 0..max {
+            let Some(digit) = self.peek_char().and_then(|c| c.to_digit(radix)) else {
+                break;
+            };
+            self.idx += 1;
+            value = Some(value.unwrap_or(0) * radix + digit);
+        }
+        value
+    }
+
+    /// `\p{Greek}`や`\pL`の名前の部分を読んで表を探す
+    fn property(&mut self) -> Result<PropertyId, RegexErrorKind> {
+        let name = match self.next_char() {
+            Some('{') => {
+                let start = self.idx;
+                let len = self.item[start..]
+                    .iter()
+                    .position(|c| *c == '}')
+                    .ok_or(RegexErrorKind::InvalidPropertyEscape)?;
+                self.idx += len + 1;
+                self.item[start..start + len].iter().collect::<String>()
+            }
+            Some(c) => c.to_string(),
+            None => return Err(RegexErrorKind::InvalidPropertyEscape),
+        };
+        PropertyId::lookup(&name).ok_or(RegexErrorKind::UnknownProperty(name))
+    }
+
+    /// `\`の後ろを読む
+    /// `\xHH`, `\u{H...}`, `\NNN` (8進数) は、その文字を表すトークンにする
+    /// 特殊記号になる文字は、エスケープした時と同じトークンにする
+    /// 数字は`Digit`にしないので、`a{\x32}`は回数指定にならない
+    /// `\p{..}`, `\P{..}`はUnicodeの表を引く
+    fn escape(&mut self) -> Result<Item, RegexErrorKind> {
+        let code = match self.peek_char() {
+            Some('p') => {
+                self.idx += 1;
+                return self.property().map(Item::SmallP);
+            }
+            Some('P') => {
+                self.idx += 1;
+                return self.property().map(Item::LargeP);
+            }
+            Some('x') => {
+                self.idx += 1;
+                let start = self.idx;
+                let code = self.digits(16, 2);
+                if self.idx - start != 2 {
+                    return Err(RegexErrorKind::InvalidHexEscape);
+                }
+                code.unwrap()
+            }
+            Some('u') => {
+                self.idx += 1;
+                if self.next_char() != Some('{') {
+                    return Err(RegexErrorKind::InvalidUnicodeEscape);
+                }
+                let code = self.digits(16, 6);
+                match (code, self.next_char()) {
+                    (Some(code), Some('}')) => code,
+                    _ => return Err(RegexErrorKind::InvalidUnicodeEscape),
+                }
+            }
+            Some('0'..='7') => self.digits(8, 3).unwrap(),
+            _ => return parse_backslash(self.next_char()),
+        };
+        char::from_u32(code)
+            .map(|c| match Item::from(c) {
+                Item::Digit(_) => Item::Char(c),
+                item => item,
+            })
+            .ok_or(RegexErrorKind::InvalidCodePoint(code))
     }
 }
 
-impl Iterator for RegexTokenIter {
-    type Item = Item;
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<(Item, usize), RegexError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.next_char() {
-            None => None,
-            Some('\\') => Some(parse_backslash(self.next_char())),
-            Some(x) => {
+        let position = self.idx;
+        if let Some(open) = self.quote {
+            // 閉じていない時は開いた`"`を指す
+            let item = self.quoted().map_err(|kind| {
+                let at = if kind == RegexErrorKind::UnclosedQuote {
+                    open
+                } else {
+                    position
+                };
+                RegexError::new(kind, self.pattern, at)
+            });
+            return Some(item.map(|item| (item, position)));
+        }
+        let item = match self.next_char()? {
+            '\\' => self.escape(),
+            '"' if self.class_depth == 0 => {
+                self.quote = Some(position);
+                Ok(Item::BracketL)
+            }
+            '{' => Ok(self.reference().unwrap_or(Item::CurryL)),
+            '/' if self.class_depth == 0 => Ok(Item::Slash),
+            '^' if position == 0 => Ok(Item::Bol),
+            '$' if self.class_depth == 0 && self.peek_char().is_none() => Ok(Item::Eol),
+            '[' if self.class_depth == 0 || self.posix_class_ahead() => {
+                if self.class_depth == 0 {
+                    self.class_first = self.idx + usize::from(self.peek_char() == Some('^'));
+                }
+                self.class_depth += 1;
+                Ok(Item::SquareL)
+            }
+            // `[]a]`や`[^]a]`のように、クラスの先頭の`]`は文字
+            ']' if self.class_depth == 1 && position == self.class_first => Ok(Item::SquareRInner),
+            ']' if self.class_depth > 0 => {
+                self.class_depth -= 1;
+                Ok(Item::SquareR)
+            }
+            x => {
                 if let Some(item) = try_special_char(x) {
-                    Some(item)
+                    Ok(item)
                 } else if let Some(item) = try_digit(x) {
-                    Some(item)
+                    Ok(item)
                 } else {
-                    Some(Item::Char(x))
+                    Ok(Item::Char(x))
                 }
             }
-        }
+        };
+        let item = item.map_err(|kind| RegexError::new(kind, self.pattern, position));
+        Some(item.map(|item| (item, position)))
     }
 }
 
-// pub fn items<T: Terminal>(iter: &mut RegexTokenIter) -> Option<NFA<T, Item>> {
-//     let mut first = rep(iter)?;
-//     match items(iter) {
-//         Some(second) => {
-//             let first_len = first.len() - 1;
-//             first.concat(first_len, second);
-//             Some(first)
-//         }
-//         None => Some(first),
-//     }
-// }
-//
-// #[derive(Debug, PartialEq)]
-// struct RepConfig {
-//     min: usize,
-//     max: Option<usize>,
-// }
-//
-// impl RepConfig {
-//     fn new(min: usize, max: Option<usize>) -> Self {
-//         Self { min, max }
-//     }
-//
-//     /// This function generates an NFA from the contents of RepConfig.
-//     // Example
-//     // { 2, 3 }
-//     //                       rep_start_idx  rep_end_idx
-//     //                             |             |
-//     //                             |             |
-//     //                            \/             \/
-//     // . --- NFA --- . --- NFA --- . --- NFA --- .
-//     //                             |            /\
-//     //                             |             |
-//     //                             |             |
-//     //                             ---------------
-//     //                                   ε
-//     // { 2,  }  rep_end_idx  rep_start_idx
-//     // . --- NFA --- . --- NFA --- .
-//     //              /\             |
-//     //               |             |
-//     //               |             |
-//     //               ---------------
-//     //                      ε
-//     // {2}
-//     // . --- NFA --- . --- NFA --- .
-//     //
-//     // number of concat nfas is max is Some -> max None -> min
-//     // rep_start_idx -> min * nfa.len()
-//     // rep_end_idx -> Some(x) -> x * nfa.len() None -> (min - 1) * nfa.len()
-//     fn nfa<T: Terminal, C: Content>(self, nfa: NFA<T, C>) -> NFA<T, C> {
-//         let base_len = nfa.len();
-//         let min = self.min;
-//         let max = match self.max {
-//             Some(x) => x,
-//             None => min - 1,
-//         };
-//         let num_nfa = usize::max(min, max);
-//         let mut nfa = nfa;
-//         nfa.concat_tail_n_times(nfa.clone(), num_nfa - 1);
-//         if min == max {
-//             return nfa;
-//         }
-//         let rep_start_idx = min * base_len - 1;
-//         let rep_end_idx = max * base_len - 1;
-//         (rep_start_idx..rep_end_idx)
-//             .step_by(base_len)
-//             .for_each(|x| nfa[x].add_epsilon(rep_end_idx));
-//         nfa
-//     }
-// }
-//
-// fn parse_rep(iter: &mut RegexTokenIter) -> Option<RepConfig> {
-//     match iter.next() {
-//         None => None,
-//         // +
-//         Some(Item::OneOrMore) => Some(RepConfig::new(1, None)),
-//         // *
-//         Some(Item::SomeTime) => Some(RepConfig::new(0, None)),
-//         // ?
-//         Some(Item::ZeroOrOne) => Some(RepConfig::new(0, Some(1))),
-//         // "{" min ","? max? "}"
-//         Some(Item::CurryL) => {
-//             let min = match iter.next() {
-//                 Some(Item::Digit(x)) => x,
-//                 _ => panic!(),
-//             };
-//             match iter.next() {
-//                 None => panic!("何かがおかしいぞい"),
-//                 // "{" min "}"
-//                 Some(Item::CurryR) => Some(RepConfig::new(min, Some(min))),
-//                 // "{" min "," max? "}"
-//                 Some(Item::Char(',')) => {
-//                     match iter.next() {
-//                         None => panic!(),
-//                         // "{" min "," max "}"
-//                         Some(Item::Digit(max)) => {
-//                             if matches!(iter.next().unwrap(), Item::CurryR) {
-//                                 // when rep config is { 5, 4 } panic!
-//                                 if max <= min {
-//                                     panic!();
-//                                 }
-//                                 Some(RepConfig::new(min, Some(max)))
-//                             } else {
-//                                 panic!();
-//                             }
-//                         }
-//                         // "{" min "," "}"
-//                         Some(Item::CurryR) => Some(RepConfig::new(min, None)),
-//                         _ => panic!(),
-//                     }
-//                 }
-//                 _ => panic!(),
-//             }
-//         }
-//         _ => {
-//             iter.back();
-//             None
-//         }
-//     }
-// }
-//
-// fn rep<T: Terminal>(iter: &mut RegexTokenIter) -> Option<NFA<T, Item>> {
-//     let item = item(iter)?;
-//     match parse_rep(iter) {
-//         Some(rep) => Some(rep.nfa(item)),
-//         None => Some(item),
-//     }
-// }
-//
-// fn item<T: Terminal>(iter: &mut RegexTokenIter) -> Option<NFA<T, Item>> {
-//     println!("{:?}", iter);
-//     match iter.next() {
-//         None | Some(Item::BracketR) => None,
-//         Some(Item::BracketL) => items(iter),
-//         Some(x) => Some(NFA::<T, Item>::from_content(x)),
-//     }
-// }
+/// トークナイズ済みの正規表現を先頭から読むイテレータ
+/// `(?i)`のように読んだ位置から効くフラグも持つ
+#[derive(Debug, Clone)]
+pub struct RegexTokenIter {
+    pattern: String,
+    item: Vec<(Item, usize)>,
+    idx: usize,
+    names: Vec<String>,
+    case_insensitive: bool,
+    definitions: Rc<Definitions>,
+    /// 展開している途中の定義の名前 循環参照の検出に使う
+    expanding: Vec<String>,
+}
+
+impl RegexTokenIter {
+    /// `Item::Reference`の名前
+    pub fn reference_name(&self, idx: usize) -> &str {
+        &self.names[idx]
+    }
+
+    /// 名前が`name`の定義を読むイテレータ
+    /// フラグと展開中の名前は引き継ぐ
+    /// 定義が無ければ`Ok(None)`、定義の正規表現が不正なら`Err`を返す
+    pub fn definition(&self, name: &str) -> Result<Option<RegexTokenIter>, RegexError> {
+        let Some(pattern) = self.definitions.get(name) else {
+            return Ok(None);
+        };
+        let mut regex = Regex::new(pattern.to_string())?;
+        regex.set_case_insensitive(self.case_insensitive);
+        regex.set_definitions(Rc::clone(&self.definitions));
+        let mut iter = regex.tokens_iter();
+        iter.expanding = self.expanding.clone();
+        iter.expanding.push(name.to_string());
+        Ok(Some(iter))
+    }
+
+    /// `name`の定義を展開している途中か
+    pub fn is_expanding(&self, name: &str) -> bool {
+        self.expanding.iter().any(|x| x == name)
+    }
+
+    /// 大文字小文字を区別せずに読んでいる途中か
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    pub fn back(&mut self) {
+        self.idx -= 1;
+    }
+
+    /// 次のトークンを消費せずに返す
+    pub fn peek(&mut self) -> Option<Item> {
+        self.item.get(self.idx).map(|(item, _)| *item)
+    }
+
+    /// 次のトークンの位置 (文字単位)
+    /// 最後まで読んでいる場合は正規表現の長さを返す
+    pub fn offset(&self) -> usize {
+        self.item
+            .get(self.idx)
+            .map_or(self.pattern.chars().count(), |(_, position)| *position)
+    }
+
+    /// 直前に読んだトークンの位置 (文字単位)
+    pub fn prev_offset(&self) -> usize {
+        self.item[self.idx - 1].1
+    }
+
+    /// `position`の位置を指すエラーを作る
+    pub fn error(&self, kind: RegexErrorKind, position: usize) -> RegexError {
+        RegexError::new(kind, &self.pattern, position)
+    }
+}
+
+impl Iterator for RegexTokenIter {
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.peek()?;
+        self.idx += 1;
+        Some(item)
+    }
+}
 
 macro_rules! check_item {
     (@define_item $arm:ident, $($arg:expr)+) => {
@@ -446,14 +822,25 @@ check_item!(@neq item_digit_char_neq, Digit, 'a', 0);
 check_item!(@eq item_any, Any, 'a',);
 check_item!(@eq item_smalld, SmallD, '0',);
 check_item!(@neq item_smalld_neq, SmallD, 'a',);
+check_item!(@eq item_smalls, SmallS, '\t',);
+check_item!(@neq item_smalls_neq, SmallS, 'a',);
+check_item!(@eq item_larges, LargeS, 'a',);
+check_item!(@neq item_larges_neq, LargeS, ' ',);
+check_item!(@eq item_smallw, SmallW, '_',);
+check_item!(@neq item_smallw_neq, SmallW, '-',);
+check_item!(@eq item_largew, LargeW, '-',);
+check_item!(@neq item_largew_neq, LargeW, 'Z',);
 check_item!(@eq item_large_d, LargeD, 'a',);
 check_item!(@neq item_larged_neq, LargeD, '0',);
 check_item!(@eq item_backslash, BackSlash, '\\',);
+check_item!(@neq item_or_never_match, Or, '|',);
+check_item!(@neq item_square_r_never_match, SquareR, ']',);
+check_item!(@eq item_square_r_inner, SquareRInner, ']',);
 
 #[test]
 fn test_parse() {
     let regex_string = "go+gle".to_string();
-    let regex = Regex::new(regex_string);
+    let regex = Regex::new(regex_string).unwrap();
     let mut regex_iter = regex.tokens_iter();
     assert_eq!(Item::Char('g'), regex_iter.next().unwrap());
     assert_eq!(Item::Char('o'), regex_iter.next().unwrap());
@@ -467,7 +854,7 @@ fn test_parse() {
 #[test]
 fn test_back() {
     let regex_string = "go+gle".to_string();
-    let regex = Regex::new(regex_string);
+    let regex = Regex::new(regex_string).unwrap();
     let mut regex_iter = regex.tokens_iter();
 
     assert_eq!(Item::Char('g'), regex_iter.next().unwrap());
@@ -486,7 +873,7 @@ fn test_back() {
 #[test]
 fn test_rep_regex() {
     let regex_string = "(abc){2,3}".to_string();
-    let regex = Regex::new(regex_string);
+    let regex = Regex::new(regex_string).unwrap();
     let mut regex_iter = regex.tokens_iter();
     assert_eq!(Item::BracketL, regex_iter.next().unwrap());
     assert_eq!(Item::Char('a'), regex_iter.next().unwrap());
@@ -501,37 +888,346 @@ fn test_rep_regex() {
     assert_eq!(None, regex_iter.next());
 }
 
+#[test]
+fn test_reference() {
+    let regex = Regex::new("{DIGIT}+{2,3}{a b}{ID}{DIGIT}".to_string()).unwrap();
+    let mut regex_iter = regex.tokens_iter();
+    assert_eq!(Item::Reference(0), regex_iter.next().unwrap());
+    assert_eq!(Item::OneOrMore, regex_iter.next().unwrap());
+    assert_eq!(Item::CurryL, regex_iter.next().unwrap());
+    assert_eq!(Item::Digit(2), regex_iter.next().unwrap());
+    assert_eq!(Item::Char(','), regex_iter.next().unwrap());
+    assert_eq!(Item::Digit(3), regex_iter.next().unwrap());
+    assert_eq!(Item::CurryR, regex_iter.next().unwrap());
+    assert_eq!(Item::CurryL, regex_iter.next().unwrap());
+    assert_eq!(Item::Char('a'), regex_iter.next().unwrap());
+    assert_eq!(Item::Char(' '), regex_iter.next().unwrap());
+    assert_eq!(Item::Char('b'), regex_iter.next().unwrap());
+    assert_eq!(Item::CurryR, regex_iter.next().unwrap());
+    assert_eq!(Item::Reference(1), regex_iter.next().unwrap());
+    assert_eq!(Item::Reference(0), regex_iter.next().unwrap());
+    assert_eq!(None, regex_iter.next());
+    assert_eq!(regex_iter.reference_name(0), "DIGIT");
+    assert_eq!(regex_iter.reference_name(1), "ID");
+}
+
+#[test]
+fn test_quoted() {
+    let regex = Regex::new(r#""a+\"\\"*[""]"#.to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let ans = vec![
+        Item::BracketL,
+        Item::Char('a'),
+        Item::Char('+'),
+        Item::Char('"'),
+        Item::Char('\\'),
+        Item::BracketR,
+        Item::SomeTime,
+        Item::SquareL,
+        Item::Char('"'),
+        Item::Char('"'),
+        Item::SquareR,
+    ];
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn test_quoted_position() {
+    let regex = Regex::new(r#"a"b\n"c"#.to_string()).unwrap();
+    let mut regex_iter = regex.tokens_iter();
+    let positions = std::iter::from_fn(|| {
+        let position = regex_iter.offset();
+        regex_iter.next().map(|item| (item, position))
+    })
+    .collect::<Vec<_>>();
+    let ans = vec![
+        (Item::Char('a'), 0),
+        (Item::BracketL, 1),
+        (Item::Char('b'), 2),
+        (Item::Char('\n'), 3),
+        (Item::BracketR, 5),
+        (Item::Char('c'), 6),
+    ];
+    assert_eq!(positions, ans);
+}
+
+#[test]
+fn test_slash() {
+    let regex = Regex::new(r#"a/"/"[/]\/"#.to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let ans = vec![
+        Item::Char('a'),
+        Item::Slash,
+        Item::BracketL,
+        Item::Char('/'),
+        Item::BracketR,
+        Item::SquareL,
+        Item::Char('/'),
+        Item::SquareR,
+        Item::Char('/'),
+    ];
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn test_leading_square_r() {
+    let tokens = |regex: &str| {
+        Regex::new(regex.to_string())
+            .unwrap()
+            .tokens_iter()
+            .collect::<Vec<_>>()
+    };
+    let ans = vec![
+        Item::SquareL,
+        Item::SquareRInner,
+        Item::Char('a'),
+        Item::SquareR,
+    ];
+    assert_eq!(tokens("[]a]"), ans);
+    let ans = vec![
+        Item::SquareL,
+        Item::Char('^'),
+        Item::SquareRInner,
+        Item::Char('a'),
+        Item::SquareR,
+    ];
+    assert_eq!(tokens("[^]a]"), ans);
+    let ans = vec![Item::SquareL, Item::Char('a'), Item::SquareR, Item::SquareR];
+    assert_eq!(tokens("[a]]"), ans);
+}
+
+#[test]
+fn test_open_posix_in_class() {
+    let tokens = |regex: &str| {
+        Regex::new(regex.to_string())
+            .unwrap()
+            .tokens_iter()
+            .collect::<Vec<_>>()
+    };
+    let class = vec![
+        Item::SquareL,
+        Item::Char('a'),
+        Item::SquareL,
+        Item::Char(':'),
+        Item::SquareR,
+    ];
+    let ans = [class.clone(), vec![Item::Slash, Item::Char('b')]].concat();
+    assert_eq!(tokens("[a[:]/b"), ans);
+    let quoted = vec![Item::BracketL, Item::Char('x'), Item::BracketR];
+    assert_eq!(tokens(r#"[a[:]"x""#), [class.clone(), quoted].concat());
+    let ans = [class, vec![Item::Char('x'), Item::Eol]].concat();
+    assert_eq!(tokens("[a[:]x$"), ans);
+    assert!(tokens(r#"[[:alpha:]"]"#).contains(&Item::Char('"')));
+}
+
+#[test]
+fn test_anchor() {
+    let regex = Regex::new(r"^a^$b$".to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let ans = vec![
+        Item::Bol,
+        Item::Char('a'),
+        Item::Char('^'),
+        Item::Char('$'),
+        Item::Char('b'),
+        Item::Eol,
+    ];
+    assert_eq!(res, ans);
+    let regex = Regex::new(r#"[$]"$"\$"#.to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    assert!(!res.contains(&Item::Eol));
+}
+
 #[test]
 fn test_baskslash() {
     let regex_string = r"\d".to_string();
-    let regex = Regex::new(regex_string);
+    let regex = Regex::new(regex_string).unwrap();
     let mut regex_iter = regex.tokens_iter();
     assert_eq!(Item::SmallD, regex_iter.next().unwrap());
 }
 
+#[test]
+fn test_control_escape() {
+    let regex = Regex::new(r"\t\n\r\f\v\0".to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let ans = ['\t', '\n', '\r', '\u{C}', '\u{B}', '\0'].map(Item::Char);
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn test_code_point_escape() {
+    let regex = Regex::new(r"\x41\u{3bb}\u{1F600}\101\0\x2A\x2d\x32".to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let ans = vec![
+        Item::Char('A'),
+        Item::Char('λ'),
+        Item::Char('😀'),
+        Item::Char('A'),
+        Item::Char('\0'),
+        Item::Ast,
+        Item::Hyphen,
+        Item::Char('2'),
+    ];
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn test_octal_stops_at_three_digits() {
+    let regex = Regex::new(r"\1012\08".to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let ans = vec![
+        Item::Char('A'),
+        Item::Digit(2),
+        Item::Char('\0'),
+        Item::Digit(8),
+    ];
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn test_property_escape() {
+    let regex = Regex::new(r"\p{Greek}\PL\p{gc=Nd}".to_string()).unwrap();
+    let res = regex.tokens_iter().collect::<Vec<_>>();
+    let lookup = |name| PropertyId::lookup(name).unwrap();
+    let ans = vec![
+        Item::SmallP(lookup("Greek")),
+        Item::LargeP(lookup("L")),
+        Item::SmallP(lookup("Nd")),
+    ];
+    assert_eq!(res, ans);
+}
+
 #[test]
 fn test_escaped_backslash() {
     let regex_string = r"\.".to_string();
-    let regex = Regex::new(regex_string);
+    let regex = Regex::new(regex_string).unwrap();
     let mut regex_iter = regex.tokens_iter();
     assert_eq!(Item::Dot, regex_iter.next().unwrap());
 }
 
-// macro_rules! rep_config {
-//     ($fn_name:ident, $regex_string:expr, $ans:expr) => {
-//         #[test]
-//         fn $fn_name() {
-//             let regex = Regex::new($regex_string);
-//             let mut regex_iter = regex.tokens_iter();
-//             let rep_config = parse_rep(&mut regex_iter).unwrap();
-//             assert_eq!($ans, rep_config);
-//         }
-//     };
-// }
+macro_rules! tokenize_error {
+    ($test_fn_name:ident, $regex_string:expr, $kind:expr, $position:expr) => {
+        #[test]
+        fn $test_fn_name() {
+            let error = Regex::new($regex_string.to_string()).err().unwrap();
+            assert_eq!(error.kind(), &$kind);
+            assert_eq!(error.position(), $position);
+        }
+    };
+}
+
+tokenize_error!(
+    invalid_escape,
+    r"ab\q",
+    RegexErrorKind::InvalidEscape('q'),
+    2
+);
+tokenize_error!(
+    trailing_backslash,
+    r"abc\",
+    RegexErrorKind::TrailingBackslash,
+    3
+);
+
+#[test]
+fn test_position() {
+    let regex = Regex::new(r"a\.b".to_string()).unwrap();
+    let mut regex_iter = regex.tokens_iter();
+    assert_eq!(0, regex_iter.offset());
+    regex_iter.next();
+    assert_eq!(1, regex_iter.offset());
+    regex_iter.next();
+    assert_eq!(3, regex_iter.offset());
+    regex_iter.next();
+    assert_eq!(4, regex_iter.offset());
+}
+tokenize_error!(unclosed_quote, r#"a"bc"#, RegexErrorKind::UnclosedQuote, 1);
+tokenize_error!(
+    class_in_quote,
+    r#""a\d""#,
+    RegexErrorKind::InvalidEscape('d'),
+    2
+);
+tokenize_error!(hex_one_digit, r"a\x4", RegexErrorKind::InvalidHexEscape, 1);
+tokenize_error!(hex_not_digit, r"\xg1", RegexErrorKind::InvalidHexEscape, 0);
+tokenize_error!(
+    unicode_no_brace,
+    r"\u3bb",
+    RegexErrorKind::InvalidUnicodeEscape,
+    0
+);
+tokenize_error!(
+    unicode_unclosed,
+    r"\u{3bb",
+    RegexErrorKind::InvalidUnicodeEscape,
+    0
+);
+tokenize_error!(
+    unicode_empty,
+    r"\u{}",
+    RegexErrorKind::InvalidUnicodeEscape,
+    0
+);
+tokenize_error!(
+    unicode_too_long,
+    r"\u{0000041}",
+    RegexErrorKind::InvalidUnicodeEscape,
+    0
+);
+tokenize_error!(
+    surrogate,
+    r"a\u{D800}",
+    RegexErrorKind::InvalidCodePoint(0xD800),
+    1
+);
+tokenize_error!(
+    too_large,
+    r"\u{110000}",
+    RegexErrorKind::InvalidCodePoint(0x110000),
+    0
+);
+tokenize_error!(
+    unknown_property,
+    r"a\p{Klingon}",
+    RegexErrorKind::UnknownProperty("Klingon".to_string()),
+    1
+);
+tokenize_error!(
+    unclosed_property,
+    r"\p{Greek",
+    RegexErrorKind::InvalidPropertyEscape,
+    0
+);
+tokenize_error!(
+    property_without_name,
+    r"ab\P",
+    RegexErrorKind::InvalidPropertyEscape,
+    2
+);
 
-// rep_config!(rep_config_struct_gen_ast, "*".to_string(), RepConfig::new(0, None));
-// rep_config!(rep_config_struct_gen_qus, "?".to_string(), RepConfig::new(0, Some(1)));
-// rep_config!(rep_config_struct_gen_plus, "+".to_string(), RepConfig::new(1, None));
-// rep_config!(rep_config_struct_gen_num, "{2}".to_string(), RepConfig::new(2, Some(2)));
-// rep_config!(rep_config_struct_gen_num_num, "{2,3}".to_string(), RepConfig::new(2, Some(3)));
-// rep_config!(rep_config_struct_gen_num_open, "{2,}".to_string(), RepConfig::new(2, None));
+#[test]
+fn test_to_pattern() {
+    let greek = PropertyId::lookup("Greek").unwrap();
+    let items = [
+        Item::Dot,
+        Item::Any,
+        Item::Plus,
+        Item::OneOrMore,
+        Item::SquareLInner,
+        Item::BackSlash,
+        Item::Caret,
+        Item::SmallD,
+        Item::LargeW,
+        Item::SmallP(greek),
+        Item::LargeP(greek),
+        Item::Char('a'),
+        Item::Digit(7),
+        Item::Slash,
+        Item::Bol,
+    ];
+    for item in items {
+        let regex = Regex::new(item.to_pattern().unwrap()).unwrap();
+        assert_eq!(regex.tokens_iter().collect::<Vec<_>>(), vec![item]);
+    }
+    assert_eq!(Item::Reference(0).to_pattern(), None);
+}
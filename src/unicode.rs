@@ -0,0 +1,202 @@
+//! `\p{..}`で使うUnicodeの文字の集合
+//! 表は`unicode_tables`に埋め込んであり、名前で引いて`PropertyId`で指す
+//!
+//! 名前は`\p{L}`, `\p{Letter}`, `\p{Greek}`, `\p{gc=Lu}`, `\p{Script=Greek}`,
+//! `\p{XID_Start}`のように書ける 大文字小文字と` `, `_`, `-`は区別しない
+use crate::char_class::CharClass;
+use crate::unicode_tables::{CASE_FOLD, PROPERTIES};
+
+pub use crate::unicode_tables::UNICODE_VERSION;
+
+/// 表の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PropertyKind {
+    /// 一般カテゴリ (`Lu`, `Letter`など)
+    GeneralCategory,
+    /// 文字種 (`Greek`, `Han`など)
+    Script,
+    /// 二値プロパティ (`XID_Start`など)
+    Binary,
+}
+
+/// 文字の集合一つ分の表
+#[derive(Debug)]
+pub struct Property {
+    pub(crate) kind: PropertyKind,
+    pub(crate) names: &'static [&'static str],
+    pub(crate) ranges: &'static [(char, char)],
+}
+
+/// `PROPERTIES`の中の表を指すインデックス
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PropertyId(usize);
+
+/// 単純ケースフォールディングで`c`と同じになる、`c`以外の文字
+/// `k`なら`K`とケルビン記号`K` (U+212A)
+pub fn case_variants(c: char) -> &'static [char] {
+    CASE_FOLD
+        .binary_search_by_key(&c, |(key, _)| *key)
+        .map_or(&[], |idx| CASE_FOLD[idx].1)
+}
+
+/// 比較用に、小文字にして` `, `_`, `-`を取り除く
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl PropertyId {
+    /// 名前から表を探す
+    pub fn lookup(name: &str) -> Option<Self> {
+        let (kinds, value) = match name.split_once(['=', ':']) {
+            Some((key, value)) => {
+                let kind = match normalize(key).as_str() {
+                    "gc" | "generalcategory" => PropertyKind::GeneralCategory,
+                    "sc" | "script" => PropertyKind::Script,
+                    _ => return None,
+                };
+                (vec![kind], value)
+            }
+            None => {
+                let kinds = vec![
+                    PropertyKind::GeneralCategory,
+                    PropertyKind::Script,
+                    PropertyKind::Binary,
+                ];
+                (kinds, name)
+            }
+        };
+        let value = normalize(value);
+        kinds.into_iter().find_map(|kind| {
+            PROPERTIES
+                .iter()
+                .position(|property| {
+                    property.kind == kind
+                        && property.names.iter().any(|name| normalize(name) == value)
+                })
+                .map(PropertyId)
+        })
+    }
+
+    fn property(self) -> &'static Property {
+        &PROPERTIES[self.0]
+    }
+
+    pub fn kind(self) -> PropertyKind {
+        self.property().kind
+    }
+
+    /// 正式な名前 (`Lu`なら`Uppercase_Letter`)
+    pub fn name(self) -> &'static str {
+        let names = self.property().names;
+        names.get(1).unwrap_or(&names[0])
+    }
+
+    pub fn ranges(self) -> &'static [(char, char)] {
+        self.property().ranges
+    }
+
+    pub fn class(self) -> CharClass {
+        CharClass::from_ranges(self.ranges().iter().copied())
+    }
+
+    pub fn contains(self, c: char) -> bool {
+        self.ranges()
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    std::cmp::Ordering::Less
+                } else if c < start {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    /// `class`とちょうど同じ文字の集合の表を探す
+    /// `\p{..}`に書き戻せるよう、`name`で引き直して同じ表になるものだけを返す
+    pub fn find(class: &CharClass) -> Option<Self> {
+        (0..PROPERTIES.len())
+            .map(PropertyId)
+            // 表を`CharClass`にする前に、両端の文字で大半をふるい落とす
+            .filter(|id| {
+                let ends = |ranges: &[(char, char)]| Some((ranges.first()?.0, ranges.last()?.1));
+                ends(id.ranges()) == ends(class.ranges())
+            })
+            .filter(|id| id.class() == *class)
+            .find(|id| Self::lookup(id.name()) == Some(*id))
+    }
+}
+
+#[cfg(test)]
+mod unicode_test {
+    use super::*;
+
+    macro_rules! lookup {
+        ($fn_name:ident, $name:expr, $ans:expr) => {
+            #[test]
+            fn $fn_name() {
+                let id = PropertyId::lookup($name).unwrap();
+                assert_eq!(id.name(), $ans);
+            }
+        };
+    }
+
+    lookup!(short_category, "Lu", "Uppercase_Letter");
+    lookup!(long_category, "Letter", "Letter");
+    lookup!(loose_name, "uppercase letter", "Uppercase_Letter");
+    lookup!(script, "Greek", "Greek");
+    lookup!(script_short, "Grek", "Greek");
+    lookup!(script_key, "Script=Greek", "Greek");
+    lookup!(script_short_key, "sc:Han", "Han");
+    lookup!(category_key, "gc=Nd", "Decimal_Number");
+    lookup!(binary, "XID_Start", "XID_Start");
+
+    #[test]
+    fn unknown() {
+        assert!(PropertyId::lookup("Klingon").is_none());
+        assert!(PropertyId::lookup("sc=Lu").is_none());
+        assert!(PropertyId::lookup("foo=Greek").is_none());
+    }
+
+    #[test]
+    fn tables_are_sorted() {
+        for property in PROPERTIES {
+            let ok = property
+                .ranges
+                .windows(2)
+                .all(|pair| pair[0].0 <= pair[0].1 && pair[0].1 < pair[1].0);
+            assert!(ok, "{:?}", property.names);
+        }
+    }
+
+    #[test]
+    fn case_variants() {
+        assert_eq!(super::case_variants('a'), &['A']);
+        assert_eq!(super::case_variants('k'), &['K', '\u{212A}']);
+        assert_eq!(super::case_variants('Σ'), &['ς', 'σ']);
+        assert!(super::case_variants('1').is_empty());
+    }
+
+    #[test]
+    fn contains() {
+        let greek = PropertyId::lookup("Greek").unwrap();
+        assert!(greek.contains('λ'));
+        assert!(!greek.contains('a'));
+        let letter = PropertyId::lookup("L").unwrap();
+        assert!(letter.contains('a'));
+        assert!(letter.contains('変'));
+        assert!(!letter.contains('1'));
+        assert_eq!(letter.class().contains('変'), letter.contains('変'));
+    }
+
+    #[test]
+    fn find() {
+        let greek = PropertyId::lookup("Grek").unwrap();
+        assert_eq!(PropertyId::find(&greek.class()), Some(greek));
+        assert_eq!(PropertyId::find(&CharClass::from_char('a')), None);
+    }
+}
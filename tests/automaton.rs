@@ -1,113 +1,84 @@
 // to test automaton, I use NFA.
-use flex::automaton::*;
 use flex::nfa::*;
-use flex::regex_parser::Item;
+use flex::regex_tokenizer::Item;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct TestTerminal;
 
-impl Terminal for TestTerminal {}
-
-type TestState = NfaState<TestTerminal, Item>;
-
 #[test]
 fn from_content() {
-    let mut ans = NFA::default();
-    let content = TestState::from_content(Item::Char('a'));
+    let mut ans = Nfa::<TestTerminal>::new();
     let mut node = NfaNode::default();
-    node.add_transition(content, 1);
-    ans.push(node);
-    ans.push(NfaNode::default());
-    let res = NFA::from_content(Item::Char('a'));
-    assert_eq!(ans, res);
-}
-
-#[test]
-fn increment_all_index() {
-    let mut ans = NFA::default();
-    let mut node1 = NfaNode::from_content(Item::Char('a'), 1);
-    node1.add_epsilon(2);
-    node1.add_terminal(TestTerminal);
-    let mut res = NFA::default();
-    res.push(node1.clone());
-    let res = res.increment_all_index(2);
-    node1.increment_all_index(2);
-    ans.push(node1);
-    assert_eq!(ans, res);
+    node.add_child(NfaEdge::Alphabet(Item::Char('a')), ans.start());
+    let idx = ans.push(node);
+    assert_eq!(idx.index(), 1);
+    assert_eq!(ans.len(), 2);
+    assert_eq!(ans[idx].edges(), &[(NfaEdge::new_char('a'), ans.start())]);
 }
 
 #[test]
 fn add_state_idx() {
-    let mut res = NFA::default();
+    let mut res = Nfa::<TestTerminal>::new();
+    let first = res.add_node();
+    let second = res.add_node();
+    res.add_child(first, NfaEdge::new_char('a'), second);
+    res.add_child(first, NfaEdge::new_epsilon(), second);
+
+    let mut ans = Nfa::new();
     let mut node = NfaNode::default();
-    node.add_content(Item::Char('a'), 1);
-    node.add_terminal(TestTerminal);
-    node.add_epsilon(2);
-    res.push(node.clone());
-    res.add_state_idx_node(0, TestState::from_content(Item::Char('b')), 3);
-    node.add_content(Item::Char('b'), 3);
-    let mut ans = NFA::default();
+    node.add_child(NfaEdge::new_char('a'), second);
+    node.add_child(NfaEdge::new_epsilon(), second);
     ans.push(node);
+    ans.push(NfaNode::default());
     assert_eq!(ans, res);
 }
 
 #[test]
 fn add_terminal_idx_node() {
-    let mut res = NFA::default();
-    let mut node = NfaNode::default();
-    node.add_content(Item::Char('a'), 1);
-    node.add_terminal(TestTerminal);
-    node.add_epsilon(2);
-    res.push(node.clone());
-    res.add_terminal_idx_node(0, TestTerminal);
-    node.add_terminal(TestTerminal);
-    let mut ans = NFA::default();
-    ans.push(node);
+    let mut res = Nfa::new();
+    let idx = res.add_terminal_node(TestTerminal);
+    let mut ans = Nfa::new();
+    ans.push(NfaNode::new_terminal(TestTerminal));
     assert_eq!(ans, res);
+    assert!(res[idx].is_terminal());
+    assert_eq!(res[idx].terminal(), Some(&TestTerminal));
 }
 
 #[test]
-fn add_content_idx_node() {
-    let mut res = NFA::default();
-    let mut node = NfaNode::default();
-    node.add_content(Item::Char('a'), 1);
-    node.add_terminal(TestTerminal);
-    node.add_epsilon(2);
-    res.push(node.clone());
-    res.add_content_idx_node(0, Item::Char('a'), 3);
-    node.add_content(Item::Char('a'), 3);
-    let mut ans = NFA::default();
-    ans.push(node);
+fn set_terminal_idx() {
+    let mut res = Nfa::new();
+    let idx = res.add_node();
+    res.add_child(res.start(), NfaEdge::new_char('a'), idx);
+    res.set_terminal(idx, TestTerminal);
+    let mut ans = Nfa::new();
+    let terminal = ans.add_terminal_node(TestTerminal);
+    ans.add_child(ans.start(), NfaEdge::new_char('a'), terminal);
     assert_eq!(ans, res);
 }
 
 #[test]
-fn set_terminal_idx() {
-    let mut res = NFA::default();
-    let mut node = NfaNode::default();
-    node.add_content(Item::Char('a'), 1);
-    node.add_terminal(TestTerminal);
-    node.add_epsilon(2);
-    res.push(node.clone());
-    res.set_terminal_to_idx(0, TestTerminal);
-    node.add_terminal(TestTerminal);
-    let mut ans = NFA::default();
-    ans.push(node);
-    assert_eq!(ans, res);
+fn set_terminal_to_last_node() {
+    let mut res = Nfa::new();
+    let first = res.add_node();
+    let last = res.add_node();
+    res.add_child(first, NfaEdge::new_char('q'), last);
+    res.set_terminal(last, TestTerminal);
+    assert!(!res[first].is_terminal());
+    assert!(res[last].is_terminal());
+    let terminals = res
+        .states()
+        .filter(|(_, node)| node.is_terminal())
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    assert_eq!(terminals, vec![last]);
 }
 
 #[test]
-fn set_terminal_to_last_node() {
-    let mut res = NFA::default();
-    let mut node1 = NfaNode::default();
-    node1.add_content(Item::Char('q'), 1);
-    let mut node2 = NfaNode::default();
-    res.push(node1.clone());
-    res.push(node2.clone());
-    res.set_termial_to_last_node(TestTerminal);
-    node2.add_terminal(TestTerminal);
-    let mut ans = NFA::default();
-    ans.push(node1);
-    ans.push(node2);
-    assert_eq!(ans, res);
+fn send_to_other_thread() {
+    let regex = flex::regex_tokenizer::Regex::new("(ab)*c".to_string()).unwrap();
+    let nfa = flex::regex_parser::parse(&regex, TestTerminal).unwrap();
+    let res = std::thread::spawn(move || nfa.collect_terminal(&['a', 'b', 'c'], 0))
+        .join()
+        .unwrap();
+    assert_eq!(res, vec![(TestTerminal, 3)]);
 }
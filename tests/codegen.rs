@@ -0,0 +1,156 @@
+// tests/generated/lexer.rs は tests/lexer_spec.toml から生成したもの
+// 生成するコードを変えたら
+// `cargo run -- -i tests/lexer_spec.toml -o tests/generated/lexer.rs` で作り直す
+use flex::codegen::generate;
+use flex::config::Configs;
+
+#[allow(dead_code)]
+mod lexer {
+    include!("generated/lexer.rs");
+}
+
+use lexer::{scan, Token};
+
+#[test]
+fn generated_is_up_to_date() {
+    let configs = Configs::from_path("./tests/lexer_spec.toml").unwrap();
+    let code = std::fs::read_to_string("./tests/generated/lexer.rs").unwrap();
+    assert_eq!(generate(&configs).unwrap(), code);
+}
+
+macro_rules! scan_test {
+    ($test_fn_name:ident, $input:expr, $ans:expr) => {
+        #[test]
+        fn $test_fn_name() {
+            assert_eq!(scan($input), $ans);
+        }
+    };
+}
+
+scan_test!(ident, "foo_1 bar", Some((Token::Ident, 5)));
+scan_test!(number, "123abc", Some((Token::Number, 3)));
+scan_test!(space, "   x", Some((Token::Space, 3)));
+scan_test!(arrow, "->x", Some((Token::Arrow, 2)));
+scan_test!(longest, "iffy", Some((Token::Ident, 4)));
+scan_test!(no_match, "-x", None);
+scan_test!(non_ascii, "λ", None);
+scan_test!(empty, "", None);
+scan_test!(keyword, "if x", Some((Token::If, 2)));
+scan_test!(label, "foo: x", Some((Token::Label, 3)));
+scan_test!(not_label, "foo :", Some((Token::Ident, 3)));
+scan_test!(directive, "#include x", Some((Token::Directive, 8)));
+scan_test!(continuation, "\\\nx", Some((Token::Continuation, 1)));
+scan_test!(continuation_at_end, "\\", Some((Token::Continuation, 1)));
+scan_test!(not_continuation, "\\x", None);
+
+#[test]
+fn lexer() {
+    use lexer::{LexToken, Lexer, Span};
+    let res = Lexer::new("if x->λ 12").collect::<Vec<_>>();
+    let ans = vec![
+        (LexToken::Token(Token::If), Span { start: 0, end: 2 }),
+        (LexToken::Token(Token::Space), Span { start: 2, end: 3 }),
+        (LexToken::Token(Token::Ident), Span { start: 3, end: 4 }),
+        (LexToken::Token(Token::Arrow), Span { start: 4, end: 6 }),
+        (LexToken::Error, Span { start: 6, end: 8 }),
+        (LexToken::Token(Token::Space), Span { start: 8, end: 9 }),
+        (LexToken::Token(Token::Number), Span { start: 9, end: 11 }),
+        (LexToken::Token(Token::Eof), Span { start: 11, end: 11 }),
+    ];
+    assert_eq!(res, ans);
+}
+scan_test!(newline_is_space, "\n\t x", Some((Token::Space, 3)));
+scan_test!(exclusive_rule_is_inactive, "*/", None);
+
+#[test]
+fn scan_in_state() {
+    use lexer::{scan_in, State};
+    assert_eq!(
+        scan_in(State::Comment, false, "*/x"),
+        Some((Token::CommentEnd, 2))
+    );
+    assert_eq!(
+        scan_in(State::Comment, false, "if x"),
+        Some((Token::CommentText, 4))
+    );
+    assert_eq!(
+        scan_in(State::Comment, false, "/*"),
+        Some((Token::CommentText, 1))
+    );
+}
+
+#[test]
+fn scan_not_at_bol() {
+    use lexer::{scan_in, State};
+    assert_eq!(scan_in(State::Initial, false, "#if"), None);
+    assert_eq!(
+        scan_in(State::Initial, true, "#if"),
+        Some((Token::Directive, 3))
+    );
+}
+
+#[test]
+fn lexer_bol() {
+    use lexer::{LexToken, Lexer};
+    let res = Lexer::new("#a #b\n#c")
+        .map(|(token, _)| token)
+        .collect::<Vec<_>>();
+    let ans = vec![
+        LexToken::Token(Token::Directive),
+        LexToken::Token(Token::Space),
+        LexToken::Error,
+        LexToken::Token(Token::Ident),
+        LexToken::Token(Token::Space),
+        LexToken::Token(Token::Directive),
+        LexToken::Token(Token::Eof),
+    ];
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn lexer_states() {
+    use lexer::{LexToken, Lexer, State};
+    let input = "x/* if */y";
+    let mut lexer = Lexer::new(input);
+    let mut res = Vec::new();
+    while let Some((token, span)) = lexer.next() {
+        match token {
+            LexToken::Token(Token::CommentStart) => lexer.push_state(State::Comment),
+            LexToken::Token(Token::CommentEnd) => {
+                assert_eq!(lexer.pop_state(), Some(State::Initial))
+            }
+            _ => {}
+        }
+        res.push((token, &input[span.start..span.end]));
+    }
+    let ans = vec![
+        (LexToken::Token(Token::Ident), "x"),
+        (LexToken::Token(Token::CommentStart), "/*"),
+        (LexToken::Token(Token::CommentText), " if "),
+        (LexToken::Token(Token::CommentEnd), "*/"),
+        (LexToken::Token(Token::Ident), "y"),
+        (LexToken::Token(Token::Eof), ""),
+    ];
+    assert_eq!(res, ans);
+    assert_eq!(lexer.state(), State::Initial);
+}
+
+#[test]
+fn lexer_unterminated_comment() {
+    use lexer::{LexToken, Lexer, Span, State};
+    let mut lexer = Lexer::new("/* x");
+    assert_eq!(
+        lexer.next().unwrap().0,
+        LexToken::Token(Token::CommentStart)
+    );
+    lexer.begin(State::Comment);
+    assert_eq!(lexer.next().unwrap().0, LexToken::Token(Token::CommentText));
+    assert_eq!(
+        lexer.next(),
+        Some((
+            LexToken::EofError("unterminated block comment"),
+            Span { start: 4, end: 4 }
+        ))
+    );
+    assert_eq!(lexer.next(), None);
+}
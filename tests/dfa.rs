@@ -0,0 +1,300 @@
+use flex::dfa::Dfa;
+use flex::nfa::Nfa;
+use flex::regex_parser;
+use flex::regex_tokenizer::Regex;
+
+/// 複数のルールを宣言順に一つのNFAにまとめる
+fn rules_nfa(rules: &[(&str, &'static str)]) -> Nfa<&'static str> {
+    let mut nfa = Nfa::new();
+    for (regex, terminal) in rules {
+        let regex = Regex::new(regex.to_string()).unwrap();
+        regex_parser::parse_into(&mut nfa, &regex, *terminal).unwrap();
+    }
+    nfa
+}
+
+/// NFAで受理した位置を昇順で返す
+fn nfa_positions(nfa: &Nfa<&'static str>, query: &[char]) -> Vec<usize> {
+    let mut res = nfa
+        .collect_terminal(query, 0)
+        .into_iter()
+        .map(|(_, idx)| idx)
+        .collect::<Vec<_>>();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/// DFAとNFAが同じ位置で受理することを確かめる
+macro_rules! same_as_nfa {
+    ($test_fn_name:ident, $regex:expr, $($query:expr),+) => {
+        #[test]
+        fn $test_fn_name() {
+            let nfa = rules_nfa(&[($regex, "Terminal")]);
+            let dfa = Dfa::from_nfa(&nfa);
+            $(
+                let query = $query.chars().collect::<Vec<_>>();
+                let res = dfa
+                    .collect_terminal(&query, 0)
+                    .into_iter()
+                    .map(|(_, idx)| idx)
+                    .collect::<Vec<_>>();
+                assert_eq!(res, nfa_positions(&nfa, &query), "query: {:?}", $query);
+            )+
+        }
+    };
+}
+
+#[cfg(test)]
+mod same_as_nfa {
+    use super::*;
+
+    same_as_nfa!(concat, "abc", "abc", "abd", "ab", "");
+    same_as_nfa!(alternation, "ab|ac|b", "ab", "ac", "b", "a");
+    same_as_nfa!(star, "(ab)*c", "c", "abc", "ababc", "abab", "aba");
+    same_as_nfa!(plus, "a+b?", "a", "aab", "aaabb", "b");
+    same_as_nfa!(counted, "a{2,4}", "a", "aa", "aaaa", "aaaaa");
+    same_as_nfa!(class, "[a-z_][a-z0-9_]*", "foo_1", "_", "1foo", "fOo");
+    same_as_nfa!(negated_class, "[^0-9]+", "ab", "a1", "λx", "");
+    same_as_nfa!(
+        overlapping,
+        "[a-m]x|[h-z]y|hz",
+        "ax",
+        "hx",
+        "hy",
+        "hz",
+        "zy"
+    );
+    same_as_nfa!(digit, r"\d+\D", "12a", "1", "a");
+    same_as_nfa!(any, "a.*b", "ab", "axxb", "abxb", "a");
+    same_as_nfa!(escaped, r"\.\*\[", ".*[", ".*", "a*[");
+    same_as_nfa!(epsilon_loop, "(a*)*b", "b", "aaab", "aaa");
+}
+
+#[cfg(test)]
+mod priority {
+    use super::*;
+
+    #[test]
+    fn keyword_before_ident() {
+        let nfa = rules_nfa(&[("if", "If"), ("[a-z]+", "Ident")]);
+        let dfa = Dfa::from_nfa(&nfa);
+        let query = ['i', 'f', 'x'];
+        assert_eq!(
+            dfa.collect_terminal(&query, 0),
+            vec![("Ident", 1), ("If", 2), ("Ident", 3)]
+        );
+    }
+
+    #[test]
+    fn ident_before_keyword() {
+        let nfa = rules_nfa(&[("[a-z]+", "Ident"), ("if", "If")]);
+        let dfa = Dfa::from_nfa(&nfa);
+        assert_eq!(
+            dfa.collect_terminal(&['i', 'f'], 0),
+            vec![("Ident", 1), ("Ident", 2)]
+        );
+    }
+
+    #[test]
+    fn start_from_middle() {
+        let nfa = rules_nfa(&[("ab", "Ab"), ("b+", "B")]);
+        let dfa = Dfa::from_nfa(&nfa);
+        assert_eq!(
+            dfa.collect_terminal(&['a', 'b', 'b'], 1),
+            vec![("B", 2), ("B", 3)]
+        );
+    }
+
+    #[test]
+    fn dead_after_mismatch() {
+        let nfa = rules_nfa(&[("ab", "Ab")]);
+        let dfa = Dfa::from_nfa(&nfa);
+        let state = dfa.next(dfa.start(), 'b');
+        assert!(dfa.is_dead(state));
+        assert!(dfa.collect_terminal(&['b', 'a', 'b'], 0).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod minimize {
+    use super::*;
+
+    /// 最小化の前後で受理する位置とterminalが変わらないことを確かめる
+    fn assert_same(rules: &[(&str, &'static str)], queries: &[&str]) -> Dfa<&'static str> {
+        let dfa = Dfa::from_nfa(&rules_nfa(rules));
+        let minimized = dfa.minimize();
+        for query in queries {
+            let query = query.chars().collect::<Vec<_>>();
+            assert_eq!(
+                minimized.collect_terminal(&query, 0),
+                dfa.collect_terminal(&query, 0)
+            );
+        }
+        minimized
+    }
+
+    #[test]
+    fn textbook() {
+        // (a|b)*abb の最小DFAは4状態 + 死状態
+        let dfa = assert_same(&[("(a|b)*abb", "T")], &["abb", "aabb", "babb", "abab"]);
+        assert_eq!(dfa.len(), 5);
+    }
+
+    #[test]
+    fn common_suffix() {
+        let dfa = assert_same(&[("ab|cb", "T")], &["ab", "cb", "b", "abb"]);
+        assert_eq!(dfa.len(), 4);
+    }
+
+    #[test]
+    fn different_terminals_are_not_merged() {
+        let dfa = assert_same(&[("a", "A"), ("b", "B")], &["a", "b"]);
+        assert_eq!(dfa.len(), 4);
+        let a = dfa.next(dfa.start(), 'a');
+        let b = dfa.next(dfa.start(), 'b');
+        assert_ne!(a, b);
+        assert_eq!(dfa.terminal(a), Some(&"A"));
+        assert_eq!(dfa.terminal(b), Some(&"B"));
+    }
+
+    #[test]
+    fn keywords() {
+        let rules = [
+            ("if", "If"),
+            ("in", "In"),
+            ("int", "Int"),
+            ("for", "For"),
+            ("[a-z]+", "Ident"),
+            ("[0-9]+", "Number"),
+        ];
+        let dfa = assert_same(
+            &rules,
+            &["if", "int", "inte", "fo", "for", "forx", "123", "i1"],
+        );
+        assert!(dfa.len() <= Dfa::from_nfa(&rules_nfa(&rules)).len());
+    }
+
+    #[test]
+    fn nothing_matches() {
+        let dfa = assert_same(&[("[^\0-\u{10FFFF}]", "T")], &["a", ""]);
+        assert_eq!(dfa.len(), 1);
+        assert!(dfa.is_dead(dfa.start()));
+    }
+
+    #[test]
+    fn idempotent() {
+        let dfa = Dfa::from_nfa(&rules_nfa(&[
+            ("[a-z_][a-z0-9_]*", "Ident"),
+            ("[0-9]+", "Num"),
+        ]));
+        let minimized = dfa.minimize();
+        assert_eq!(minimized.minimize(), minimized);
+    }
+}
+
+#[cfg(test)]
+mod trailing_context {
+    use super::*;
+
+    fn longest_match(rules: &[(&str, &'static str)], input: &str) -> Option<(&'static str, usize)> {
+        let dfa = Dfa::from_nfa(&rules_nfa(rules)).minimize();
+        dfa.longest_match(input)
+            .map(|(terminal, len)| (*terminal, len))
+    }
+
+    #[test]
+    fn fixed_tail() {
+        let rules = [("[0-9]+/\"..\"", "Int"), ("[0-9]+\\.[0-9]*", "Float")];
+        assert_eq!(longest_match(&rules, "1..2"), Some(("Int", 1)));
+        assert_eq!(longest_match(&rules, "1.5"), Some(("Float", 3)));
+        assert_eq!(longest_match(&rules, "12."), Some(("Float", 3)));
+    }
+
+    #[test]
+    fn fixed_head() {
+        let rules = [("ab/c+", "Ab"), ("[a-c]+", "Word")];
+        assert_eq!(longest_match(&rules, "abcc"), Some(("Ab", 2)));
+        assert_eq!(longest_match(&rules, "abd"), Some(("Word", 2)));
+    }
+
+    #[test]
+    fn compared_with_whole_length() {
+        // r/sはrs全体の長さで最長一致を比べる
+        let rules = [("a+", "A"), ("a/aaa", "Short")];
+        assert_eq!(longest_match(&rules, "aaaa"), Some(("A", 4)));
+        let rules = [("a/aaa", "Short"), ("a+", "A")];
+        assert_eq!(longest_match(&rules, "aaaa"), Some(("Short", 1)));
+    }
+
+    #[test]
+    fn byte_length() {
+        let rules = [("λ+/\"→\"", "Lambda")];
+        assert_eq!(longest_match(&rules, "λλ→"), Some(("Lambda", 4)));
+    }
+}
+
+#[cfg(test)]
+mod anchor {
+    use super::*;
+
+    same_as_nfa!(bol, "^ab", "ab", "xab", "");
+    same_as_nfa!(eol, "a+$", "aa", "aa\n", "aab", "");
+    same_as_nfa!(bol_and_eol, "^a|b$", "a", "b", "ba");
+
+    #[test]
+    fn bol_only_at_line_start() {
+        let nfa = rules_nfa(&[("^#", "Directive"), ("#", "Hash")]);
+        let dfa = Dfa::from_nfa(&nfa).minimize();
+        let query = ['#', '#', '\n', '#'];
+        assert_eq!(dfa.collect_terminal(&query, 0), vec![("Directive", 1)]);
+        assert_eq!(dfa.collect_terminal(&query, 1), vec![("Hash", 2)]);
+        assert_eq!(dfa.collect_terminal(&query, 3), vec![("Directive", 4)]);
+        assert_eq!(dfa.longest_match("#"), Some((&"Directive", 1)));
+        assert_eq!(dfa.longest_match_from(dfa.start(), "#"), Some((&"Hash", 1)));
+    }
+
+    #[test]
+    fn eol_before_newline_or_end() {
+        let rules = [("[a-z]+$", "Last"), ("[a-z]+", "Word")];
+        let dfa = Dfa::from_nfa(&rules_nfa(&rules)).minimize();
+        assert_eq!(dfa.longest_match("ab\ncd"), Some((&"Last", 2)));
+        assert_eq!(dfa.longest_match("ab"), Some((&"Last", 2)));
+        assert_eq!(dfa.longest_match("ab cd"), Some((&"Word", 2)));
+    }
+
+    #[test]
+    fn priority_at_end() {
+        // 先に書いたルールが同じ長さで一致するなら、入力の終わりでもそちらが勝つ
+        let rules = [("[a-z]+", "Word"), ("[a-z]+$", "Last")];
+        let dfa = Dfa::from_nfa(&rules_nfa(&rules)).minimize();
+        assert_eq!(dfa.longest_match("ab"), Some((&"Word", 2)));
+        let rules = [("ab$", "Last"), ("[a-z]+", "Word")];
+        let dfa = Dfa::from_nfa(&rules_nfa(&rules)).minimize();
+        assert_eq!(dfa.longest_match("ab"), Some((&"Last", 2)));
+        assert_eq!(dfa.longest_match("abc"), Some((&"Word", 3)));
+    }
+}
+
+#[cfg(test)]
+mod unicode {
+    use super::*;
+
+    same_as_nfa!(
+        identifier,
+        r"\p{XID_Start}\p{XID_Continue}*",
+        "λx1_変数",
+        "1x",
+        "_x",
+        "Ωmega"
+    );
+    same_as_nfa!(not_greek, r"\P{Greek}+", "abλ", "λ", "");
+
+    #[test]
+    fn minimized_identifier_is_small() {
+        let nfa = rules_nfa(&[(r"\p{XID_Start}\p{XID_Continue}*", "Ident")]);
+        let dfa = Dfa::from_nfa(&nfa).minimize();
+        // 死状態, 開始状態, 識別子の途中 の3つ
+        assert_eq!(dfa.len(), 3);
+    }
+}
@@ -0,0 +1,243 @@
+// flexで生成したコード 手で編集しないこと
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token {
+    If,
+    Ident,
+    Number,
+    Space,
+    Arrow,
+    Label,
+    Directive,
+    Continuation,
+    CommentStart,
+    CommentEnd,
+    CommentText,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    Initial,
+    Comment,
+}
+
+const STARTS: [usize; 2] = [1, 2];
+const BOL_STARTS: [usize; 2] = [3, 2];
+const EOF: [Option<LexToken>; 2] = [Some(LexToken::Token(Token::Eof)), Some(LexToken::EofError("unterminated block comment"))];
+const DEAD: usize = 0;
+
+#[rustfmt::skip]
+const BOUNDARIES: [char; 31] = [
+    '\0', '\t', '\n', '\u{b}', '\u{e}', ' ', '!', '#',
+    '$', '*', '+', '-', '.', '/', '0', ':',
+    ';', '>', '?', 'A', '[', '\\', ']', '_',
+    '`', 'a', 'f', 'g', 'i', 'j', '{',
+];
+
+#[rustfmt::skip]
+const TRANSITIONS: [u8; 651] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 7, 7, 7, 0, 7, 0, 0, 0, 0, 0, 8, 0, 10, 6, 0, 0, 0, 0, 5, 0, 9, 0, 5, 0, 5, 5, 5, 4, 5, 0,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 11, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+    0, 7, 7, 7, 0, 7, 0, 13, 0, 0, 0, 8, 0, 10, 6, 0, 0, 0, 0, 5, 0, 9, 0, 5, 0, 5, 5, 5, 4, 5, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 15, 0, 0, 0, 5, 0, 0, 0, 5, 0, 5, 14, 5, 5, 5, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 15, 0, 0, 0, 5, 0, 0, 0, 5, 0, 5, 5, 5, 5, 5, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 7, 7, 7, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 17, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 19, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 0, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 20, 20, 20, 20, 20, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 15, 0, 0, 0, 5, 0, 0, 0, 5, 0, 5, 5, 5, 5, 5, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 20, 20, 20, 20, 20, 0,
+];
+
+#[rustfmt::skip]
+const ACCEPTS: [Option<Token>; 21] = [
+    None, None, None, None,
+    Some(Token::Ident), Some(Token::Ident), Some(Token::Number), Some(Token::Space),
+    None, None, None, Some(Token::CommentText),
+    Some(Token::CommentText), None, Some(Token::If), Some(Token::Label),
+    Some(Token::Arrow), Some(Token::Continuation), Some(Token::CommentStart), Some(Token::CommentEnd),
+    Some(Token::Directive),
+];
+
+#[rustfmt::skip]
+const AT_END: [Option<Token>; 21] = [
+    None, None, None, None,
+    None, None, None, None,
+    None, Some(Token::Continuation), None, None,
+    None, None, None, None,
+    None, None, None, None,
+    None,
+];
+
+#[rustfmt::skip]
+const TRAILING: [Option<Trailing>; 21] = [
+    None, None, None, None, None, None, None, None,
+    None, None, None, None, None, None, None, Some(Trailing::Tail(1)),
+    None, Some(Trailing::Tail(1)), None, None, None,
+];
+
+/// `r/s`のルールで、`r`と`s`のどちらの長さ (文字数) が決まっているか
+/// `r/s`のルールが無い仕様では使わない
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+enum Trailing {
+    Head(usize),
+    Tail(usize),
+}
+
+fn class_of(c: char) -> usize {
+    BOUNDARIES.partition_point(|&boundary| boundary <= c) - 1
+}
+
+/// `r/s`に一致した`matched`のうち、`r`の部分のバイト数
+fn token_len(matched: &str, trailing: Trailing) -> usize {
+    match trailing {
+        Trailing::Head(len) => matched
+            .char_indices()
+            .nth(len)
+            .map_or(matched.len(), |(offset, _)| offset),
+        Trailing::Tail(0) => matched.len(),
+        Trailing::Tail(len) => matched
+            .char_indices()
+            .nth_back(len - 1)
+            .map_or(0, |(offset, _)| offset),
+    }
+}
+
+/// `input`の先頭から最長一致するトークンと、そのバイト数を返す
+/// `input`は行頭から始まるものとして読む
+/// 同じ長さで一致するルールが複数あれば、仕様で先に書いたものを返す
+/// `r/s`のルールは`rs`で最長一致を決め、`r`の部分だけをトークンにする
+pub fn scan(input: &str) -> Option<(Token, usize)> {
+    scan_in(State::Initial, true, input)
+}
+
+/// スタート状態`start`で有効なルールだけで`scan`する
+/// `at_bol`が`true`なら`^`のルールも使う
+pub fn scan_in(start: State, at_bol: bool, input: &str) -> Option<(Token, usize)> {
+    let mut state = if at_bol {
+        BOL_STARTS[start as usize]
+    } else {
+        STARTS[start as usize]
+    };
+    let mut last = ACCEPTS[state].map(|token| (token, TRAILING[state], 0));
+    for (offset, c) in input.char_indices() {
+        state = TRANSITIONS[state * BOUNDARIES.len() + class_of(c)] as usize;
+        if state == DEAD {
+            break;
+        }
+        if let Some(token) = ACCEPTS[state] {
+            last = Some((token, TRAILING[state], offset + c.len_utf8()));
+        }
+    }
+    if let Some(token) = AT_END[state] {
+        last = Some((token, None, input.len()));
+    }
+    let (token, trailing, len) = last?;
+    match trailing {
+        Some(trailing) => Some((token, token_len(&input[..len], trailing))),
+        None => Some((token, len)),
+    }
+}
+
+/// 入力の中の範囲 (バイト単位)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexToken {
+    Token(Token),
+    /// どのルールにも一致しなかった
+    Error,
+    /// 入力の終わりで`<<EOF>>`のルールが報告したエラー
+    EofError(&'static str),
+}
+
+/// 最長一致でトークンを切り出し、(トークン, 範囲) を返す
+/// どのルールにも一致しなければ、一文字を`LexToken::Error`として返す
+/// 入力を読み終えたら、今のスタート状態の`EOF`を空の範囲で一度だけ返す
+pub struct Lexer<'a> {
+    input: &'a str,
+    position: usize,
+    state: State,
+    stack: Vec<State>,
+    finished: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            position: 0,
+            state: State::Initial,
+            stack: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// 次に読む位置 (バイト単位)
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 今のスタート状態
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// スタート状態を`state`に切り替える
+    pub fn begin(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// 今のスタート状態を積んでから`state`に切り替える
+    pub fn push_state(&mut self, state: State) {
+        self.stack.push(self.state);
+        self.state = state;
+    }
+
+    /// `push_state`の前のスタート状態に戻し、戻った状態を返す
+    /// 積んだ状態が無ければ何もせず`None`を返す
+    pub fn pop_state(&mut self) -> Option<State> {
+        self.state = self.stack.pop()?;
+        Some(self.state)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = (LexToken, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.position..];
+        let start = self.position;
+        let Some(c) = rest.chars().next() else {
+            if self.finished {
+                return None;
+            }
+            self.finished = true;
+            let token = EOF[self.state as usize]?;
+            return Some((token, Span { start, end: start }));
+        };
+        let at_bol = start == 0 || self.input[..start].ends_with('\n');
+        let (token, len) = match scan_in(self.state, at_bol, rest) {
+            Some((token, len)) if len > 0 => (LexToken::Token(token), len),
+            _ => (LexToken::Error, c.len_utf8()),
+        };
+        self.position += len;
+        Some((token, Span { start, end: self.position }))
+    }
+}
@@ -0,0 +1,307 @@
+use flex::config::Configs;
+use flex::dfa::Dfa;
+use flex::lexer::{LexToken, Lexer, Span};
+
+fn dfa(toml: &str) -> Dfa<usize> {
+    Configs::from_toml(toml).unwrap().dfa().unwrap()
+}
+
+/// `input`を字句解析し、(ルールの番号, 切り出した文字列) を返す
+/// エラーはルールの番号を`None`にする
+fn lex(toml: &str, input: &str) -> Vec<(Option<usize>, String)> {
+    let dfa = dfa(toml);
+    Lexer::new(&dfa, input)
+        .map(|(token, span)| {
+            let token = match token {
+                LexToken::Token(rule) => Some(rule),
+                LexToken::Error => None,
+            };
+            (token, input[span.start..span.end].to_string())
+        })
+        .collect()
+}
+
+macro_rules! lex_test {
+    ($test_fn_name:ident, $toml:expr, $input:expr, [$(($rule:expr, $str:expr)),* $(,)?]) => {
+        #[test]
+        fn $test_fn_name() {
+            let ans: Vec<(Option<usize>, String)> = vec![$(($rule, $str.to_string())),*];
+            assert_eq!(lex($toml, $input), ans);
+        }
+    };
+}
+
+const KEYWORDS: &str = r#"
+[If]
+regex = "if"
+[Int]
+regex = "int"
+[Ident]
+regex = "[a-z]+"
+[Space]
+regex = "[ ]+"
+"#;
+
+lex_test!(
+    keyword_wins_tie,
+    KEYWORDS,
+    "if int",
+    [(Some(0), "if"), (Some(3), " "), (Some(1), "int")]
+);
+lex_test!(
+    longest_match_wins,
+    KEYWORDS,
+    "iffy integer",
+    [(Some(2), "iffy"), (Some(3), " "), (Some(2), "integer")]
+);
+lex_test!(
+    error_token,
+    KEYWORDS,
+    "a1b",
+    [(Some(2), "a"), (None, "1"), (Some(2), "b")]
+);
+lex_test!(
+    error_is_one_char,
+    KEYWORDS,
+    "λ→",
+    [(None, "λ"), (None, "→")]
+);
+lex_test!(empty_input, KEYWORDS, "", []);
+
+// 最長一致の途中で失敗したら、最後に受理した位置まで戻る
+lex_test!(
+    backtrack_to_last_accept,
+    "[Dot]\nregex = \"\\\\.\"\n[Ellipsis]\nregex = \"\\\\.\\\\.\\\\.\"",
+    "..",
+    [(Some(0), "."), (Some(0), ".")]
+);
+
+// 空文字列に一致するルールでは先に進めないのでエラーにする
+lex_test!(
+    empty_match_is_error,
+    "[A]\nregex = \"a*\"",
+    "aab",
+    [(Some(0), "aa"), (None, "b")]
+);
+
+// `1..2`は範囲、`1.5`や`1.`は小数
+const RANGE: &str = r#"
+[Int]
+regex = "[0-9]+/\"..\""
+[Float]
+regex = "[0-9]+\\.[0-9]*"
+[Number]
+regex = "[0-9]+"
+[Range]
+regex = "\"..\""
+"#;
+
+lex_test!(
+    trailing_context_range,
+    RANGE,
+    "1..2",
+    [(Some(0), "1"), (Some(3), ".."), (Some(2), "2")]
+);
+lex_test!(
+    trailing_context_float,
+    RANGE,
+    "1.5 1.",
+    [(Some(1), "1.5"), (None, " "), (Some(1), "1.")]
+);
+
+// 文字列の中では`Escape`と`Text`だけが有効
+const MODES: &str = r#"
+[states]
+Str = "exclusive"
+[Quote]
+regex = '\"'
+states = ["*"]
+[Ident]
+regex = "[a-z]+"
+[Escape]
+regex = '\\.'
+states = ["Str"]
+[Text]
+regex = '[^"\\]+'
+states = ["Str"]
+"#;
+
+/// `Quote`で文字列の中と外を切り替えながら字句解析する
+fn lex_modes(input: &str) -> Vec<(usize, String)> {
+    let dfa = dfa(MODES);
+    let mut lexer = Lexer::new(&dfa, input);
+    let mut res = Vec::new();
+    while let Some((token, span)) = lexer.next() {
+        let LexToken::Token(rule) = token else {
+            panic!("error at {:?}", span);
+        };
+        if rule == 0 {
+            if lexer.state() == 0 {
+                assert!(lexer.push_state(1));
+            } else {
+                assert_eq!(lexer.pop_state(), Some(0));
+            }
+        }
+        res.push((rule, input[span.start..span.end].to_string()));
+    }
+    res
+}
+
+#[test]
+fn start_states() {
+    let res = lex_modes(r#"ab"c d\"e"f"#);
+    let ans = [
+        (1, "ab"),
+        (0, "\""),
+        (3, "c d"),
+        (2, "\\\""),
+        (3, "e"),
+        (0, "\""),
+        (1, "f"),
+    ];
+    let ans = ans.map(|(rule, s)| (rule, s.to_string()));
+    assert_eq!(res, ans);
+}
+
+#[test]
+fn begin_and_pop() {
+    let dfa = dfa(MODES);
+    let mut lexer = Lexer::new(&dfa, "ab");
+    assert_eq!(lexer.pop_state(), None);
+    assert!(lexer.begin(1));
+    assert_eq!(lexer.state(), 1);
+    assert!(!lexer.begin(2));
+    assert!(!lexer.push_state(2));
+    assert_eq!(lexer.state(), 1);
+    assert_eq!(lexer.pop_state(), None);
+    assert_eq!(lexer.next().unwrap().0, LexToken::Token(3));
+}
+
+// `^#`は行頭でだけ、`[a-z]+$`は行末でだけ一致する
+const ANCHORS: &str = r##"
+[Directive]
+regex = "^#[a-z]+"
+[Hash]
+regex = "#"
+[Last]
+regex = "[a-z]+$"
+[Word]
+regex = "[a-z]+"
+[Space]
+regex = '[ \n]+'
+"##;
+
+lex_test!(
+    anchors,
+    ANCHORS,
+    "#if a\n #b c",
+    [
+        (Some(0), "#if"),
+        (Some(4), " "),
+        (Some(2), "a"),
+        (Some(4), "\n "),
+        (Some(1), "#"),
+        (Some(3), "b"),
+        (Some(4), " "),
+        (Some(2), "c"),
+    ]
+);
+lex_test!(
+    bol_after_newline,
+    ANCHORS,
+    "a\n#b",
+    [(Some(2), "a"), (Some(4), "\n"), (Some(0), "#b")]
+);
+
+// コメントの中で入力が終わったらエラーにする
+const EOFS: &str = r#"
+[states]
+Comment = "exclusive"
+[Open]
+regex = '"/*"'
+[Close]
+regex = '"*/"'
+states = ["Comment"]
+[Text]
+regex = '[^*]+|\*'
+states = ["Comment"]
+[Word]
+regex = "[a-z]+"
+[Eof]
+regex = "<<EOF>>"
+[Unterminated]
+regex = "<<EOF>>"
+states = ["Comment"]
+error = "unterminated block comment"
+"#;
+
+/// `Open`と`Close`でコメントに出入りしながら字句解析する
+fn lex_comments(input: &str) -> Vec<(usize, String)> {
+    let dfa = dfa(EOFS);
+    let mut lexer = Lexer::new(&dfa, input);
+    let mut res = Vec::new();
+    while let Some((token, span)) = lexer.next() {
+        let LexToken::Token(rule) = token else {
+            panic!("error at {:?}", span);
+        };
+        match rule {
+            0 => assert!(lexer.begin(1)),
+            1 => assert!(lexer.begin(0)),
+            _ => {}
+        }
+        res.push((rule, input[span.start..span.end].to_string()));
+    }
+    res
+}
+
+#[test]
+fn eof_token() {
+    let res = lex_comments("a/*b*/");
+    let ans = [(3, "a"), (0, "/*"), (2, "b"), (1, "*/"), (4, "")];
+    let ans = ans.map(|(rule, s)| (rule, s.to_string()));
+    assert_eq!(res, ans);
+    assert_eq!(lex_comments(""), vec![(4, String::new())]);
+}
+
+#[test]
+fn eof_in_comment() {
+    let res = lex_comments("a/*b");
+    assert_eq!(res.last(), Some(&(5, String::new())));
+    let configs = Configs::from_toml(EOFS).unwrap();
+    assert_eq!(
+        configs.rules()[5].error(),
+        Some("unterminated block comment")
+    );
+}
+
+#[test]
+fn eof_only_once() {
+    let dfa = dfa(EOFS);
+    let mut lexer = Lexer::new(&dfa, "a");
+    assert_eq!(lexer.next(), Some((LexToken::Token(3), Span::new(0, 1))));
+    assert_eq!(lexer.next(), Some((LexToken::Token(4), Span::new(1, 1))));
+    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn span_is_byte_offset() {
+    let dfa = dfa("[Lambda]\nregex = \"λ+\"");
+    let res = Lexer::new(&dfa, "λλx").collect::<Vec<_>>();
+    assert_eq!(
+        res,
+        vec![
+            (LexToken::Token(0), Span::new(0, 4)),
+            (LexToken::Error, Span::new(4, 5)),
+        ]
+    );
+}
+
+#[test]
+fn position() {
+    let dfa = dfa(KEYWORDS);
+    let mut lexer = Lexer::new(&dfa, "if x");
+    assert_eq!(lexer.position(), 0);
+    lexer.next();
+    assert_eq!(lexer.position(), 2);
+}
@@ -1,6 +1,830 @@
+use flex::regex_error::{RegexError, RegexErrorKind};
+use flex::regex_tokenizer::Regex;
+
+/// 正規表現`regex`を`query`の先頭から走らせ、受理した位置を昇順で返す
+fn accepted_positions(regex: &str, query: &str) -> Vec<usize> {
+    let regex = Regex::new(regex.to_string()).unwrap();
+    let nfa = flex::regex_parser::parse(&regex, "Terminal").unwrap();
+    let query = query.chars().collect::<Vec<_>>();
+    let mut res = nfa
+        .collect_terminal(&query, 0)
+        .into_iter()
+        .map(|(_, idx)| idx)
+        .collect::<Vec<_>>();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/// トークナイズかパースで起きたエラーを返す
+fn parse_error(regex: &str) -> RegexError {
+    Regex::new(regex.to_string())
+        .and_then(|regex| flex::regex_parser::parse(&regex, "Terminal"))
+        .err()
+        .unwrap()
+}
+
+macro_rules! error_test {
+    ($test_fn_name:ident, $regex:expr, $kind:expr, $position:expr) => {
+        #[test]
+        fn $test_fn_name() {
+            let error = parse_error($regex);
+            assert_eq!(error.kind(), &$kind);
+            assert_eq!(error.position(), $position);
+        }
+    };
+}
+
+macro_rules! accept_test {
+    ($test_fn_name:ident, $regex:expr, $query:expr, $ans:expr) => {
+        #[test]
+        fn $test_fn_name() {
+            assert_eq!(accepted_positions($regex, $query), $ans);
+        }
+    };
+}
 
 #[cfg(test)]
 mod alphabet {
+    use super::*;
+    use flex::char_class::CharClass;
+    use flex::regex_ast::RegexAst;
     use flex::regex_parser;
 
+    #[test]
+    fn operator_is_not_alphabet() {
+        let regex = Regex::new("*".to_string()).unwrap();
+        let res = regex_parser::alphabet(&mut regex.tokens_iter());
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn single_char() {
+        let regex = Regex::new("a".to_string()).unwrap();
+        let res = regex_parser::alphabet(&mut regex.tokens_iter());
+        assert_eq!(res, Some(RegexAst::Literal('a')));
+    }
+
+    #[test]
+    fn any_is_class() {
+        let regex = Regex::new(".".to_string()).unwrap();
+        let res = regex_parser::alphabet(&mut regex.tokens_iter());
+        assert_eq!(res, Some(RegexAst::Class(CharClass::any())));
+    }
+
+    accept_test!(char_match, "a", "a", vec![1]);
+    accept_test!(char_unmatch, "a", "b", vec![]);
+    accept_test!(digit, "1", "1", vec![1]);
+    accept_test!(any, ".", "x", vec![1]);
+    accept_test!(escaped_dot, r"\.", ".", vec![1]);
+    accept_test!(escaped_dot_unmatch, r"\.", "x", vec![]);
+    accept_test!(escaped_plus, r"\+", "+", vec![1]);
+    accept_test!(escaped_bracket, r"\(\)", "()", vec![2]);
+    accept_test!(small_d, r"\d", "7", vec![1]);
+}
+
+#[cfg(test)]
+mod concat {
+    use super::*;
+
+    accept_test!(word, "google", "google", vec![6]);
+    accept_test!(prefix_only, "google", "goo", vec![]);
+    accept_test!(longer_query, "go", "google", vec![2]);
+    accept_test!(empty_regex, "", "abc", vec![0]);
+}
+
+#[cfg(test)]
+mod alternation {
+    use super::*;
+
+    accept_test!(left, "ab|cd", "ab", vec![2]);
+    accept_test!(right, "ab|cd", "cd", vec![2]);
+    accept_test!(neither, "ab|cd", "ad", vec![]);
+    accept_test!(three, "a|b|c", "c", vec![1]);
+    accept_test!(empty_branch, "a|", "a", vec![0, 1]);
+    accept_test!(different_length, "a|abc", "abc", vec![1, 3]);
+}
+
+#[cfg(test)]
+mod group {
+    use super::*;
+
+    accept_test!(group, "(ab)c", "abc", vec![3]);
+    accept_test!(group_or, "x(ab|cd)y", "xcdy", vec![4]);
+    accept_test!(nested, "((a)(b))", "ab", vec![2]);
+    accept_test!(empty_group, "a()b", "ab", vec![2]);
+    error_test!(unclosed, "(ab", RegexErrorKind::UnclosedGroup, 0);
+    error_test!(unopened, "ab)", RegexErrorKind::UnopenedGroup, 2);
+}
+
+#[cfg(test)]
+mod rep {
+    use super::*;
+
+    accept_test!(some_time_zero, "ab*", "a", vec![1]);
+    accept_test!(some_time_many, "ab*", "abbb", vec![1, 2, 3, 4]);
+    accept_test!(one_or_more_zero, "ab+", "a", vec![]);
+    accept_test!(one_or_more_many, "ab+", "abbb", vec![2, 3, 4]);
+    accept_test!(zero_or_one, "ab?c", "ac", vec![2]);
+    accept_test!(zero_or_one_once, "ab?c", "abc", vec![3]);
+    accept_test!(zero_or_one_twice, "ab?c", "abbc", vec![]);
+    accept_test!(group_star, "(ab)*", "ababa", vec![0, 2, 4]);
+    accept_test!(group_or_plus, "(a|b)+c", "abbac", vec![5]);
+    accept_test!(google, "go+gle", "gooogle", vec![7]);
+    accept_test!(stacked, "a+?", "aa", vec![0, 1, 2]);
+}
+
+#[cfg(test)]
+mod counted_rep {
+    use super::*;
+
+    accept_test!(exact, "a{3}", "aaaa", vec![3]);
+    accept_test!(exact_short, "a{3}", "aa", vec![]);
+    accept_test!(exact_zero, "ba{0}", "ba", vec![1]);
+    accept_test!(open, "a{2,}", "aaaa", vec![2, 3, 4]);
+    accept_test!(open_zero, "a{0,}", "aa", vec![0, 1, 2]);
+    accept_test!(range, "a{2,3}", "aaaa", vec![2, 3]);
+    accept_test!(range_same, "a{2,2}", "aaa", vec![2]);
+    accept_test!(range_zero, "a{0,2}", "aaa", vec![0, 1, 2]);
+    accept_test!(group, "(ab){2}", "ababab", vec![4]);
+    accept_test!(group_or, "(a|bc){1,2}", "bca", vec![2, 3]);
+    accept_test!(with_star, "(ab*){2}", "abba", vec![4]);
+    accept_test!(followed, "x{2}y", "xxy", vec![3]);
+    accept_test!(stacked, "(a{2}){2}", "aaaaa", vec![4]);
+
+    #[test]
+    fn multi_digit() {
+        let query = "a".repeat(300);
+        assert_eq!(
+            accepted_positions("a{10,255}", &query),
+            (10..=255).collect::<Vec<_>>()
+        );
+        assert_eq!(accepted_positions("a{12}", &query), vec![12]);
+    }
+    error_test!(
+        max_smaller_than_min,
+        "a{3,2}",
+        RegexErrorKind::InvalidRepetitionRange(3, 2),
+        1
+    );
+    error_test!(unclosed, "a{3", RegexErrorKind::UnclosedRepetition, 1);
+    error_test!(no_min, "a{,3}", RegexErrorKind::MissingRepetitionMin, 2);
+    error_test!(
+        min_over_limit,
+        "a{1001}",
+        RegexErrorKind::RepetitionTooLarge,
+        2
+    );
+    error_test!(
+        max_over_limit,
+        "a{2,1001}",
+        RegexErrorKind::RepetitionTooLarge,
+        4
+    );
+
+    #[test]
+    fn at_limit() {
+        let query = "a".repeat(1001);
+        assert_eq!(accepted_positions("a{1000}", &query), vec![1000]);
+    }
+}
+
+#[cfg(test)]
+mod char_class {
+    use super::*;
+
+    accept_test!(single, "[abc]", "b", vec![1]);
+    accept_test!(single_unmatch, "[abc]", "d", vec![]);
+    accept_test!(range, "[a-z]", "q", vec![1]);
+    accept_test!(range_unmatch, "[a-z]", "Q", vec![]);
+    accept_test!(digit_range, "[0-9]+", "2022x", vec![1, 2, 3, 4]);
+    accept_test!(multi_range, "[a-z0-9_]", "_", vec![1]);
+    accept_test!(negated, "[^a-z]", "A", vec![1]);
+    accept_test!(negated_unmatch, "[^a-z]", "a", vec![]);
+    accept_test!(caret_not_first, "[a^]", "^", vec![1]);
+    accept_test!(special_chars, "[.*+?|(){}]", "*", vec![1]);
+    accept_test!(dot_is_literal, "[.]", "a", vec![]);
+    accept_test!(escaped_bracket, r"[\]]", "]", vec![1]);
+    accept_test!(escaped_hyphen, r"[a\-z]", "-", vec![1]);
+    accept_test!(escaped_hyphen_not_range, r"[a\-z]", "b", vec![]);
+    accept_test!(leading_hyphen, "[-a]", "-", vec![1]);
+    accept_test!(trailing_hyphen, "[a-]", "-", vec![1]);
+    accept_test!(shorthand, r"[\da-f]+", "3fz", vec![1, 2]);
+    accept_test!(negated_shorthand, r"[^\d]", "7", vec![]);
+    accept_test!(counted, "[0-9]{2,3}", "1234", vec![2, 3]);
+    accept_test!(leading_bracket, "[]a]+", "]a]b", vec![1, 2, 3]);
+    accept_test!(negated_leading_bracket, "[^]a]", "]", vec![]);
+    accept_test!(negated_leading_bracket_other, "[^]a]", "b", vec![1]);
+
+    #[test]
+    fn identifier() {
+        let regex = "[A-Za-z_][A-Za-z0-9_]*";
+        assert_eq!(accepted_positions(regex, "_foo1 bar"), vec![1, 2, 3, 4, 5]);
+        assert_eq!(accepted_positions(regex, "1foo"), vec![]);
+    }
+    error_test!(unclosed, "[a-z", RegexErrorKind::UnclosedClass, 0);
+    error_test!(empty, "[]", RegexErrorKind::UnclosedClass, 0);
+    error_test!(
+        out_of_order,
+        "[z-a]",
+        RegexErrorKind::InvalidClassRange('z', 'a'),
+        1
+    );
+}
+
+#[cfg(test)]
+mod shorthand {
+    use super::*;
+
+    accept_test!(space, r"\s+", " \t\n\r\u{B}\u{C}x", vec![1, 2, 3, 4, 5, 6]);
+    accept_test!(space_unmatch, r"\s", "a", vec![]);
+    accept_test!(not_space, r"\S+", "ab c", vec![1, 2]);
+    accept_test!(word, r"\w+", "a_Z9-", vec![1, 2, 3, 4]);
+    accept_test!(not_word, r"\W", "-", vec![1]);
+    accept_test!(not_word_unmatch, r"\W", "_", vec![]);
+    accept_test!(in_class, r"[\s,]+", ", \t,x", vec![1, 2, 3, 4]);
+    accept_test!(negated_in_class, r"[^\w\s]", "+", vec![1]);
+    accept_test!(tab, r"a\tb", "a\tb", vec![3]);
+    accept_test!(newline, r"\r?\n", "\r\n", vec![2]);
+    accept_test!(nul, r"\0", "\0", vec![1]);
+    accept_test!(control_in_class, r"[\t\n]+", "\n\t ", vec![1, 2]);
+    error_test!(
+        shorthand_range_end,
+        r"[a-\s]",
+        RegexErrorKind::InvalidClassRangeEnd,
+        3
+    );
+}
+
+#[cfg(test)]
+mod code_point {
+    use super::*;
+
+    accept_test!(hex, r"\x41\x62", "Ab", vec![2]);
+    accept_test!(unicode, r"\u{2192}+", "→→", vec![1, 2]);
+    accept_test!(octal, r"\101", "A", vec![1]);
+    accept_test!(escaped_meta_is_literal, r"a\x2A", "a*", vec![2]);
+    accept_test!(escaped_meta_not_repeat, r"a\x2A", "aa", vec![]);
+    accept_test!(class_range, r"[\x41-\x5A]+", "AZq", vec![1, 2]);
+    accept_test!(
+        class_unicode_range,
+        r"[\u{3b1}-\u{3c9}]+",
+        "λμA",
+        vec![1, 2]
+    );
+    accept_test!(class_mixed_range, r"[\x61-z]", "q", vec![1]);
+    accept_test!(class_caret_not_negation, r"[\x5E]", "^", vec![1]);
+    accept_test!(class_hyphen_not_range, r"[a\x2Dz]", "b", vec![]);
+    accept_test!(class_hyphen_literal, r"[a\x2Dz]", "-", vec![1]);
+    accept_test!(escaped_caret, r"\^", "^", vec![1]);
+    accept_test!(escaped_digit_is_literal, r"a\x32", "a2", vec![2]);
+    error_test!(
+        escaped_digit_not_count,
+        r"a{\x32}",
+        RegexErrorKind::InvalidRepetition,
+        2
+    );
+    error_test!(
+        class_range_out_of_order,
+        r"[\u{3c9}-\u{3b1}]",
+        RegexErrorKind::InvalidClassRange('ω', 'α'),
+        1
+    );
+    error_test!(
+        invalid_code_point,
+        r"[a\u{D800}]",
+        RegexErrorKind::InvalidCodePoint(0xD800),
+        2
+    );
+}
+
+#[cfg(test)]
+mod unicode_property {
+    use super::*;
+
+    accept_test!(letter, r"\p{L}+", "aλ変1", vec![1, 2, 3]);
+    accept_test!(one_letter_name, r"\pL", "λ", vec![1]);
+    accept_test!(not_letter, r"\PL+", "12a", vec![1, 2]);
+    accept_test!(greek, r"\p{Greek}+", "αβγa", vec![1, 2, 3]);
+    accept_test!(script_key, r"\p{Script=Han}", "漢", vec![1]);
+    accept_test!(decimal_number, r"\p{Nd}+", "1٣४x", vec![1, 2, 3]);
+    accept_test!(in_class, r"[\p{Greek}\d]+", "α1a", vec![1, 2]);
+    accept_test!(negated_in_class, r"[^\p{L}]", "λ", vec![]);
+    accept_test!(not_in_class, r"[\P{L}]", "-", vec![1]);
+    accept_test!(
+        identifier,
+        r"\p{XID_Start}\p{XID_Continue}*",
+        "λx1_変数 ",
+        vec![1, 2, 3, 4, 5, 6]
+    );
+    accept_test!(identifier_digit_start, r"\p{XID_Start}", "1", vec![]);
+    error_test!(
+        range_end,
+        r"[a-\p{L}]",
+        RegexErrorKind::InvalidClassRangeEnd,
+        3
+    );
+}
+
+#[cfg(test)]
+mod posix_class {
+    use super::*;
+
+    accept_test!(alpha, "[[:alpha:]]+", "aZ1", vec![1, 2]);
+    accept_test!(digit, "[[:digit:]]+", "09a", vec![1, 2]);
+    accept_test!(space, "[[:space:]]+", " \t\nx", vec![1, 2, 3]);
+    accept_test!(upper_lower, "[[:upper:]][[:lower:]]+", "Abc", vec![2, 3]);
+    accept_test!(xdigit, "[[:xdigit:]]+", "fF9g", vec![1, 2, 3]);
+    accept_test!(punct, "[[:punct:]]+", "!_~a", vec![1, 2, 3]);
+    accept_test!(blank, "[[:blank:]]", "\n", vec![]);
+    accept_test!(cntrl, "[[:cntrl:]]", "\u{7F}", vec![1]);
+    accept_test!(graph_print, "[[:graph:]][[:print:]]", "a ", vec![2]);
+    accept_test!(alnum, "[[:alnum:]]+", "a1_", vec![1, 2]);
+    accept_test!(with_range, "[[:digit:]a-f]+", "9fg", vec![1, 2]);
+    accept_test!(
+        with_char,
+        "[_[:alpha:]][_[:alnum:]]*",
+        "_a1-",
+        vec![1, 2, 3]
+    );
+    accept_test!(negated, "[^[:alpha:]]", "a", vec![]);
+    accept_test!(negated_match, "[^[:alpha:]_]", "1", vec![1]);
+    accept_test!(negated_name, "[[:^digit:]]", "a", vec![1]);
+    accept_test!(two_classes, "[[:digit:][:upper:]]+", "1A", vec![1, 2]);
+    accept_test!(bracket_literal, "[[a]", "[", vec![1]);
+    accept_test!(not_posix, "[[:a]", ":", vec![1]);
+    error_test!(
+        unknown,
+        "a[[:alfa:]]",
+        RegexErrorKind::UnknownPosixClass("alfa".to_string()),
+        2
+    );
+    error_test!(
+        range_end,
+        "[a-[:digit:]]",
+        RegexErrorKind::InvalidClassRangeEnd,
+        3
+    );
+}
+
+#[cfg(test)]
+mod case_insensitive {
+    use super::*;
+
+    accept_test!(whole, "(?i)abc", "ABC", vec![3]);
+    accept_test!(mixed, "(?i)abc", "aBc", vec![3]);
+    accept_test!(after, "a(?i)b", "aB", vec![2]);
+    accept_test!(before_is_sensitive, "a(?i)b", "Ab", vec![]);
+    accept_test!(scoped, "(?i:a)b", "Ab", vec![2]);
+    accept_test!(scoped_end, "(?i:a)b", "AB", vec![]);
+    accept_test!(inside_group, "((?i)a)b", "AB", vec![]);
+    accept_test!(disable, "(?i)a(?-i)b", "AB", vec![]);
+    accept_test!(disable_match, "(?i)a(?-i)b", "Ab", vec![2]);
+    accept_test!(class, "(?i)[a-c]+", "AbC", vec![1, 2, 3]);
+    accept_test!(negated_class, "(?i)[^a]", "A", vec![]);
+    accept_test!(kelvin, "(?i)k", "\u{212A}", vec![1]);
+    accept_test!(sigma, "(?i)σ", "ς", vec![1]);
+    accept_test!(digit_unchanged, r"(?i)\d", "1", vec![1]);
+    accept_test!(with_rep, "(?i)a+", "aAa", vec![1, 2, 3]);
+    error_test!(unknown_flag, "a(?x)", RegexErrorKind::InvalidFlag, 3);
+    error_test!(lookahead, "(?=a)", RegexErrorKind::InvalidFlag, 2);
+    error_test!(unclosed, "(?i", RegexErrorKind::UnclosedGroup, 0);
+    error_test!(unclosed_scoped, "(?i:a", RegexErrorKind::UnclosedGroup, 0);
+}
+
+#[cfg(test)]
+mod quoted {
+    use super::*;
+
+    accept_test!(operator, r#""a+b""#, "a+b", vec![3]);
+    accept_test!(operator_unmatch, r#""a+b""#, "aab", vec![]);
+    accept_test!(shift_assign, r#""<<=""#, "<<=", vec![3]);
+    accept_test!(comment_open, r#""/*""#, "/*", vec![2]);
+    accept_test!(with_class, r#""0x"[0-9a-f]+"#, "0x1fz", vec![3, 4]);
+    accept_test!(repeated, r#""ab"+"#, "abab", vec![2, 4]);
+    accept_test!(escaped_quote, r#""\"""#, "\"", vec![1]);
+    accept_test!(escaped_backslash, r#""\\""#, "\\", vec![1]);
+    accept_test!(control_escape, r#""\t""#, "\t", vec![1]);
+    accept_test!(empty, r#"a""b"#, "ab", vec![2]);
+    accept_test!(string_literal, r#"\"[^"]*\""#, r#""a+b" x"#, vec![5]);
+    accept_test!(brackets, r#""[(""#, "[(", vec![2]);
+    accept_test!(case_insensitive, r#"(?i)"if""#, "IF", vec![2]);
+    error_test!(unclosed, r#"ab"cd"#, RegexErrorKind::UnclosedQuote, 2);
+}
+
+#[cfg(test)]
+mod trailing_context {
+    use super::*;
+
+    // NFAはr/sの区切りを気にせず、rs全体に一致した位置を返す
+    accept_test!(whole_match, "ab/c", "abc", vec![3]);
+    accept_test!(needs_tail, "ab/c", "abd", vec![]);
+    accept_test!(variable_head, "[0-9]+/\"..\"", "12..", vec![4]);
+    accept_test!(variable_tail, "a/b+", "abb", vec![2, 3]);
+    accept_test!(alternation_head, "ab|cd/x", "abx", vec![3]);
+    accept_test!(class_slash, "[/]/a", "/a", vec![2]);
+    accept_test!(escaped_slash, r"a\/b", "a/b", vec![3]);
+    accept_test!(quoted_slash, r#""/*"/x"#, "/*x", vec![3]);
+    error_test!(
+        both_variable,
+        "a+/b*",
+        RegexErrorKind::VariableTrailingContext,
+        2
+    );
+    error_test!(
+        in_group,
+        "(a/b)",
+        RegexErrorKind::MisplacedTrailingContext,
+        2
+    );
+    error_test!(twice, "a/b/c", RegexErrorKind::MisplacedTrailingContext, 3);
+    error_test!(
+        in_flag_group,
+        "(?i:a/b)",
+        RegexErrorKind::MisplacedTrailingContext,
+        5
+    );
+}
+
+#[cfg(test)]
+mod anchor {
+    use super::*;
+
+    // queryの先頭は行頭として読む
+    accept_test!(bol, "^ab", "ab", vec![2]);
+    accept_test!(caret_in_middle, "a^", "a^", vec![2]);
+    accept_test!(negated_class, "^[^a]", "b", vec![1]);
+    accept_test!(escaped_caret, r"\^a", "^a", vec![2]);
+    // NFAは`r$`の改行まで含めた位置と、入力の終わりの位置を返す
+    accept_test!(eol_before_newline, "ab$", "ab\n", vec![3]);
+    accept_test!(eol_at_end, "ab$", "ab", vec![2]);
+    accept_test!(eol_needs_end, "ab$", "abc", vec![]);
+    accept_test!(eol_star, "a*$", "aa", vec![2]);
+    accept_test!(eol_star_newline, "a*$", "a\n", vec![2]);
+    accept_test!(both, "^a|b$", "b", vec![1]);
+    accept_test!(dollar_in_middle, "a$b", "a$b", vec![3]);
+    accept_test!(dollar_in_class, "a[$]", "a$", vec![2]);
+    accept_test!(escaped_dollar, r"a\$", "a$", vec![2]);
+    accept_test!(quoted_dollar, r#"a"$""#, "a$", vec![2]);
+    accept_test!(dollar_in_group, "(a$)", "a$", vec![2]);
+    accept_test!(group_eol, "(a|b)$", "b", vec![1]);
+    error_test!(
+        with_trailing_context,
+        "a/b$",
+        RegexErrorKind::MisplacedAnchor,
+        3
+    );
+}
+
+#[cfg(test)]
+mod ast {
+    use super::*;
+    use flex::char_class::CharClass;
+    use flex::regex_ast::{Anchor, RegexAst};
+    use flex::regex_parser::parse_ast;
+
+    fn lit(c: char) -> RegexAst {
+        RegexAst::Literal(c)
+    }
+
+    fn class(ranges: &[(char, char)]) -> RegexAst {
+        RegexAst::Class(CharClass::from_ranges(ranges.iter().copied()))
+    }
+
+    fn group(inner: RegexAst) -> RegexAst {
+        RegexAst::Group(Box::new(inner))
+    }
+
+    fn repeat(inner: RegexAst, min: usize, max: Option<usize>) -> RegexAst {
+        RegexAst::Repeat {
+            inner: Box::new(inner),
+            min,
+            max,
+        }
+    }
+
+    macro_rules! ast_test {
+        ($test_fn_name:ident, $regex:expr, $ans:expr) => {
+            #[test]
+            fn $test_fn_name() {
+                let regex = Regex::new($regex.to_string()).unwrap();
+                assert_eq!(parse_ast(&regex).unwrap(), $ans);
+            }
+        };
+    }
+
+    ast_test!(empty, "", RegexAst::empty());
+    ast_test!(single, "a", lit('a'));
+    ast_test!(concat, "ab", RegexAst::Concat(vec![lit('a'), lit('b')]));
+    ast_test!(
+        alternate,
+        "a|bc",
+        RegexAst::Alternate(vec![lit('a'), RegexAst::Concat(vec![lit('b'), lit('c')])])
+    );
+    ast_test!(star, "(a)*", repeat(group(lit('a')), 0, None));
+    ast_test!(counted, "a{2,3}", repeat(lit('a'), 2, Some(3)));
+    ast_test!(
+        nested_repeat,
+        "a+?",
+        repeat(repeat(lit('a'), 1, None), 0, Some(1))
+    );
+    ast_test!(char_class, "[a-c_]", class(&[('_', '_'), ('a', 'c')]));
+    ast_test!(shorthand, r"\d", class(&[('0', '9')]));
+    ast_test!(escaped, r"\.", lit('.'));
+    ast_test!(case_insensitive, "(?i)a", class(&[('A', 'A'), ('a', 'a')]));
+    ast_test!(
+        flag_is_dropped,
+        "a(?i)b",
+        RegexAst::Concat(vec![lit('a'), class(&[('B', 'B'), ('b', 'b')])])
+    );
+    ast_test!(
+        quoted,
+        r#""a+""#,
+        group(RegexAst::Concat(vec![lit('a'), lit('+')]))
+    );
+    ast_test!(
+        anchors,
+        "^a|b$",
+        RegexAst::Concat(vec![
+            RegexAst::Anchor(Anchor::StartLine),
+            group(RegexAst::Alternate(vec![lit('a'), lit('b')])),
+            RegexAst::Anchor(Anchor::EndLine),
+        ])
+    );
+    ast_test!(
+        trailing,
+        "ab/c",
+        RegexAst::Trailing(
+            Box::new(RegexAst::Concat(vec![lit('a'), lit('b')])),
+            Box::new(lit('c'))
+        )
+    );
+}
+
+#[cfg(test)]
+mod canonical {
+    use super::*;
+    use flex::regex_parser::parse_ast;
+
+    /// 構文木を書き戻した正規表現が`$ans`になり、パースし直すと同じ構文木になる
+    macro_rules! canonical_test {
+        ($test_fn_name:ident, $regex:expr, $ans:expr) => {
+            #[test]
+            fn $test_fn_name() {
+                let ast = parse_ast(&Regex::new($regex.to_string()).unwrap()).unwrap();
+                let printed = ast.to_string();
+                assert_eq!(printed, $ans);
+                let reparsed = parse_ast(&Regex::new(printed).unwrap()).unwrap();
+                assert_eq!(reparsed, ast);
+            }
+        };
+    }
+
+    canonical_test!(plain, "abc", "abc");
+    canonical_test!(escaped, r"a\.b\*\(\)", r"a\.b\*\(\)");
+    canonical_test!(needless_escape, r"\-\x41\u{3bb}", "-Aλ");
+    canonical_test!(quoted, r#""/*""#, r"(\/\*)");
+    canonical_test!(quoted_repeat, r#""ab"+"a"*"#, "(ab)+(a)*");
+    canonical_test!(group, "x(ab)(c|d)(e)", "x(ab)(c|d)(e)");
+    canonical_test!(nested_alternate, "a|(b|c)", "a|(b|c)");
+    canonical_test!(quote_char, r#"\"a\"\$"#, r#"\"a\"\$"#);
+    canonical_test!(control, r"\t\x00", r"\t\u{0}");
+    canonical_test!(merged_class, "[a-cb-dx]", "[a-dx]");
+    canonical_test!(two_chars, "[a-b]", "[ab]");
+    canonical_test!(single_char_class, "[a]", "[a]");
+    canonical_test!(shorthand, "[0-9]+", r"\d+");
+    canonical_test!(word, "[a-zA-Z0-9_]", r"\w");
+    canonical_test!(negated, r"[^\n]", r"[^\n]");
+    canonical_test!(negated_shorthand, "[^0-9]", r"\D");
+    canonical_test!(any, ".", ".");
+    canonical_test!(property, r"\p{Grek}\P{L}", r"\p{Greek}\P{Letter}");
+    canonical_test!(class_escapes, r"[\]\\\-^.]", r"[\-.\\-^]");
+    canonical_test!(leading_caret, r"[\^a]", r"[\^a]");
+    canonical_test!(empty_class, "[^\0-\u{10FFFF}]", r"[^\u{0}-\u{10ffff}]");
+    canonical_test!(
+        counted,
+        "a{2}b{2,}c{2,3}d{0,1}e{0,}f{1,}",
+        "a{2}b{2,}c{2,3}d?e*f+"
+    );
+    canonical_test!(nested_repeat, "(a+)?b+?", "(a+)?b+?");
+    canonical_test!(digits_after_repeat, "a{2}3", "a{2}3");
+    canonical_test!(alternate, "a|(b|c)d|", "a|(b|c)d|");
+    canonical_test!(case_insensitive, "(?i)ab", "[Aa][Bb]");
+    canonical_test!(anchors, "^a|b$", "^(a|b)$");
+    canonical_test!(only_anchor, "^", "^");
+    canonical_test!(literal_anchors, r"\^a$b", r"\^a\$b");
+    canonical_test!(trailing, "a+/[0-9]", r"a+/\d");
+    canonical_test!(bol_trailing, "^a|b/c", "^a|b/c");
+}
+
+#[cfg(test)]
+mod definition {
+    use super::*;
+    use flex::nfa::Nfa;
+    use flex::regex_tokenizer::Definitions;
+    use std::rc::Rc;
+
+    /// テスト用の定義
+    fn definitions() -> Rc<Definitions> {
+        let mut definitions = Definitions::new();
+        for (name, regex) in [
+            ("DIGIT", "[0-9]"),
+            ("NUM", "{DIGIT}+"),
+            ("AB", "ab|cd"),
+            ("KW", "select"),
+            ("SELF", "a{SELF}"),
+            ("PING", "a{PONG}"),
+            ("PONG", "b{PING}"),
+            ("BROKEN", "a("),
+            ("UNDEF", "{NOTHING}"),
+            ("ANCHORED", "^a"),
+        ] {
+            definitions.insert(name.to_string(), regex.to_string());
+        }
+        Rc::new(definitions)
+    }
+
+    fn parse_with_definitions(regex: &str) -> Result<Nfa<&'static str>, RegexError> {
+        let mut regex = Regex::new(regex.to_string())?;
+        regex.set_definitions(definitions());
+        flex::regex_parser::parse(&regex, "Terminal")
+    }
+
+    fn positions(regex: &str, query: &str) -> Vec<usize> {
+        let nfa = parse_with_definitions(regex).unwrap();
+        let query = query.chars().collect::<Vec<_>>();
+        let mut res = nfa
+            .collect_terminal(&query, 0)
+            .into_iter()
+            .map(|(_, idx)| idx)
+            .collect::<Vec<_>>();
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+
+    macro_rules! definition_test {
+        ($test_fn_name:ident, $regex:expr, $query:expr, $ans:expr) => {
+            #[test]
+            fn $test_fn_name() {
+                assert_eq!(positions($regex, $query), $ans);
+            }
+        };
+    }
+
+    macro_rules! definition_error {
+        ($test_fn_name:ident, $regex:expr, $kind:expr, $pattern:expr, $position:expr) => {
+            #[test]
+            fn $test_fn_name() {
+                let error = parse_with_definitions($regex).err().unwrap();
+                assert_eq!(error.kind(), &$kind);
+                assert_eq!(error.pattern(), $pattern);
+                assert_eq!(error.position(), $position);
+            }
+        };
+    }
+
+    definition_test!(single, "{DIGIT}", "7", vec![1]);
+    definition_test!(repeated, "{DIGIT}+", "123a", vec![1, 2, 3]);
+    definition_test!(nested, "{NUM}\\.{NUM}", "12.5", vec![4]);
+    definition_test!(as_group, "{AB}*x", "abcdx", vec![5]);
+    definition_test!(alternation_is_grouped, "x{AB}", "xcd", vec![3]);
+    definition_test!(counted, "{DIGIT}{2}", "123", vec![2]);
+    definition_test!(counted_after, "a{2}{DIGIT}", "aa1", vec![3]);
+    definition_test!(case_insensitive, "(?i){KW}", "SeLeCt", vec![6]);
+    definition_test!(in_class, "[{DIGIT}]+", "{IT}9", vec![1, 2, 3, 4]);
+    definition_error!(
+        undefined,
+        "ab{NOTHING}",
+        RegexErrorKind::UndefinedName("NOTHING".to_string()),
+        "ab{NOTHING}",
+        2
+    );
+    definition_error!(
+        undefined_in_definition,
+        "{UNDEF}",
+        RegexErrorKind::UndefinedName("NOTHING".to_string()),
+        "{NOTHING}",
+        0
+    );
+    definition_error!(
+        self_reference,
+        "{SELF}",
+        RegexErrorKind::RecursiveDefinition("SELF".to_string()),
+        "a{SELF}",
+        1
+    );
+    definition_error!(
+        cycle,
+        "x{PING}",
+        RegexErrorKind::RecursiveDefinition("PING".to_string()),
+        "b{PING}",
+        1
+    );
+    definition_error!(broken, "x{BROKEN}", RegexErrorKind::UnclosedGroup, "a(", 1);
+    definition_error!(
+        anchor,
+        "x{ANCHORED}",
+        RegexErrorKind::MisplacedAnchor,
+        "^a",
+        0
+    );
+
+    #[test]
+    fn expanded_as_group() {
+        use flex::regex_ast::RegexAst;
+        let mut regex = Regex::new("{KW}".to_string()).unwrap();
+        regex.set_definitions(definitions());
+        let ast = flex::regex_parser::parse_ast(&regex).unwrap();
+        let select = "select".chars().map(RegexAst::Literal).collect();
+        assert_eq!(ast, RegexAst::Group(Box::new(RegexAst::Concat(select))));
+    }
+
+    #[test]
+    fn not_a_name() {
+        let error = parse_with_definitions("a{ 1}").err().unwrap();
+        assert_eq!(error.kind(), &RegexErrorKind::InvalidRepetition);
+    }
+
+    #[test]
+    fn no_definitions() {
+        let error = parse_error("{DIGIT}");
+        assert_eq!(
+            error.kind(),
+            &RegexErrorKind::UndefinedName("DIGIT".to_string())
+        );
+    }
+}
+
+#[cfg(test)]
+mod error {
+    use super::*;
+
+    error_test!(
+        invalid_escape,
+        r"ab\q",
+        RegexErrorKind::InvalidEscape('q'),
+        2
+    );
+    error_test!(
+        trailing_backslash,
+        r"ab\",
+        RegexErrorKind::TrailingBackslash,
+        2
+    );
+    error_test!(nested_unclosed, "(a(b)", RegexErrorKind::UnclosedGroup, 0);
+    error_test!(nothing_to_repeat, "*a", RegexErrorKind::NothingToRepeat, 0);
+    error_test!(
+        nothing_to_repeat_or,
+        "a|+",
+        RegexErrorKind::NothingToRepeat,
+        2
+    );
+    error_test!(stray_square, "a]", RegexErrorKind::UnexpectedChar(']'), 1);
+    error_test!(stray_curry, "a}", RegexErrorKind::UnexpectedChar('}'), 1);
+    error_test!(invalid_rep, "a{2x}", RegexErrorKind::InvalidRepetition, 3);
+    error_test!(
+        rep_too_large,
+        "a{99999999999999999999999}",
+        RegexErrorKind::RepetitionTooLarge,
+        2
+    );
+    error_test!(
+        class_range_end,
+        r"[a-\d]",
+        RegexErrorKind::InvalidClassRangeEnd,
+        3
+    );
+    error_test!(
+        position_after_escape,
+        r"\.\.(",
+        RegexErrorKind::UnclosedGroup,
+        4
+    );
+
+    #[test]
+    fn caret_diagnostic() {
+        let error = parse_error("ab(cd");
+        assert_eq!(
+            error.to_string(),
+            "error: unclosed parenthesis\n  |\n  | ab(cd\n  |   ^"
+        );
+    }
+}
+
+#[cfg(test)]
+mod epsilon_loop {
+    use super::*;
+
+    accept_test!(nested_star, "(a*)*", "aa", vec![0, 1, 2]);
+    accept_test!(nested_star_unmatch, "(a*)*b", "aac", vec![]);
+    accept_test!(star_of_optional, "(a?)*b", "aab", vec![3]);
+    accept_test!(counted_nullable, "(a*){2,}", "aaa", vec![0, 1, 2, 3]);
+
+    #[test]
+    fn long_input() {
+        let query = "ab".repeat(20_000);
+        let res = accepted_positions("(a|b)*", &query);
+        assert_eq!(res.len(), 40_001);
+    }
 }